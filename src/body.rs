@@ -1,9 +1,13 @@
-use std::f64::consts::PI;
+use kiss3d::nalgebra::{Point3, Point4};
 
-use chrono::{DateTime, TimeZone, Timelike, Utc};
-use kiss3d::nalgebra::{Point3, Point4, Unit, UnitQuaternion, Vector3};
+mod orientation;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+pub use orientation::earth_orientation;
+
+// Gravitational constant in km^3/(kg*s^2).
+pub const G: f64 = 6.67430e-20;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Body {
     Sun,
     Earth,
@@ -11,6 +15,22 @@ pub enum Body {
 }
 
 impl Body {
+    // All the bodies in the simulation. The position of a body in this list
+    // matches its index().
+    pub const ALL: [Body; 3] = [Body::Sun, Body::Earth, Body::Moon];
+    pub const COUNT: usize = Body::ALL.len();
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    // Looks up a body by name (case insensitive).
+    pub fn from_name(name: &str) -> Option<Body> {
+        Body::ALL
+            .into_iter()
+            .find(|b| b.props().name.eq_ignore_ascii_case(name))
+    }
+
     pub fn props(&self) -> &'static BodyProperties {
         match self {
             Body::Sun => &BodyProperties::SUN,
@@ -27,6 +47,15 @@ impl Body {
         self.props().radius
     }
 
+    // Mass in kg, derived from the gravitational parameter.
+    pub fn mass(&self) -> f64 {
+        self.props().gm / G
+    }
+
+    pub fn gm(&self) -> f64 {
+        self.props().gm
+    }
+
     pub fn color3(&self) -> Point3<f32> {
         let p = self.props();
         Point3::new(p.color.0, p.color.1, p.color.2)
@@ -39,61 +68,35 @@ impl Body {
 
 pub struct BodyProperties {
     pub name: &'static str,
-    // Mass in kg.
-    pub mass: f64,
+    // Gravitational parameter (G times the mass) in km^3/s^2. It is known to
+    // many more digits than G or the mass alone.
+    pub gm: f64,
     // Radius in km.
     pub radius: f64,
     // Color for rendering (RGB).
     pub color: (f32, f32, f32),
 }
 
+// The gravitational parameters are those of the JPL DE440 ephemeris.
 impl BodyProperties {
     pub const SUN: BodyProperties = BodyProperties {
         name: "Sun",
-        mass: 1.9885e+30,
+        gm: 1.32712440041e+11,
         radius: 696342.0,
         color: (1.0, 0.8, 0.3),
     };
 
     pub const EARTH: BodyProperties = BodyProperties {
         name: "Earth",
-        mass: 5.97237e+24,
+        gm: 398600.435436,
         radius: 6378.137, // equatorial
         color: (0.1, 0.5, 1.0),
     };
 
     pub const MOON: BodyProperties = BodyProperties {
         name: "Moon",
-        mass: 7.34767309e+22,
+        gm: 4902.800066,
         radius: 1737.5,
         color: (0.7, 0.7, 0.7),
     };
 }
-
-const EARTH_TROPICAL_YEAR: f64 = 365.2412 * 24.0 * 3600.0;
-const EARTH_TILT: f64 = 23.4;
-
-// relative_earth_orientation calculates the rotation that needs to be applied
-// to Earth so that it matches the given timestamp.
-//
-// It is assumed that the Sun is directly to the left (-x axis), and that the
-// Earth's UTC timezone faces the sun.
-pub fn relative_earth_orientation(timestamp: &DateTime<Utc>) -> UnitQuaternion<f64> {
-    let (h, m, s) = (timestamp.hour(), timestamp.minute(), timestamp.second());
-    let delta_seconds = (s + 60 * (m + 60 * h)) as f64;
-
-    let rotation_angle = PI * (delta_seconds / (12.0 * 3600.0) - 1.0);
-
-    let known_solstice = Utc.ymd(2000, 6, 21).and_hms(1, 47, 43);
-    let delta = timestamp
-        .signed_duration_since(known_solstice)
-        .num_seconds() as f64;
-
-    // 0 is the summer solstice and PI is the winter solstice.
-    let axis_orientation = (delta % EARTH_TROPICAL_YEAR) / EARTH_TROPICAL_YEAR * 2.0 * PI;
-    // At angle 0, we have to rotate around the y axis vector.
-    let axis = Vector3::new(axis_orientation.sin(), axis_orientation.cos(), 0.0);
-
-    UnitQuaternion::from_axis_angle(&Unit::new_normalize(axis), -EARTH_TILT.to_radians())
-        * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), rotation_angle)
-}
@@ -0,0 +1,178 @@
+// Orientation of the Earth in the simulation frame, which is the ecliptic and
+// equinox of J2000 (the frame of the Horizons vectors).
+//
+// The model combines the IAU 1976 precession, the main terms of the IAU 1980
+// nutation and the IAU 1982 Greenwich sidereal time, as described in Meeus,
+// "Astronomical Algorithms" (chapters 12, 21 and 22). It is accurate to about
+// an arcsecond over a few centuries. Polar motion and the difference between
+// UT1 and UTC are ignored.
+
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Utc};
+use kiss3d::nalgebra::{UnitQuaternion, Vector3};
+
+use crate::time::{julian_centuries, julian_date, J2000};
+
+const ARCSEC: f64 = PI / (180.0 * 3600.0);
+
+// Mean obliquity at J2000.
+const J2000_OBLIQUITY: f64 = 84381.448 * ARCSEC;
+
+// Returns the rotation from the Earth-fixed frame (z through the north pole,
+// Greenwich meridian along -x, see geo) to the simulation frame.
+pub fn earth_orientation(timestamp: &DateTime<Utc>) -> UnitQuaternion<f64> {
+    let rot_x = |angle: f64| UnitQuaternion::from_axis_angle(&Vector3::x_axis(), angle);
+    let rot_z = |angle: f64| UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle);
+
+    let obliquity = mean_obliquity(timestamp);
+    let nutation = nutation(timestamp);
+    // Equator and equinox of date, to mean equator and equinox of date.
+    let nutation =
+        rot_x(obliquity) * rot_z(-nutation.longitude) * rot_x(-(obliquity + nutation.obliquity));
+    // Equator of J2000 to the ecliptic of J2000.
+    let equator_to_ecliptic = rot_x(-J2000_OBLIQUITY);
+
+    // The Greenwich meridian is at the sidereal time east of the equinox of
+    // date, and along -x in the Earth-fixed frame.
+    equator_to_ecliptic
+        * precession(timestamp)
+        * nutation
+        * rot_z(greenwich_apparent_sidereal_time(timestamp) + PI)
+}
+
+// Greenwich mean sidereal time, in radians in [0, 2*PI).
+pub fn greenwich_mean_sidereal_time(timestamp: &DateTime<Utc>) -> f64 {
+    let days = julian_date(timestamp) - J2000;
+    let t = days / 36525.0;
+    let degrees =
+        280.46061837 + 360.98564736629 * days + 0.000387933 * t * t - t * t * t / 38710000.0;
+    degrees.to_radians().rem_euclid(2.0 * PI)
+}
+
+// Greenwich apparent sidereal time (measured from the true equinox of date), in
+// radians in [0, 2*PI).
+pub fn greenwich_apparent_sidereal_time(timestamp: &DateTime<Utc>) -> f64 {
+    let nutation = nutation(timestamp);
+    let equation_of_equinoxes =
+        nutation.longitude * (mean_obliquity(timestamp) + nutation.obliquity).cos();
+    (greenwich_mean_sidereal_time(timestamp) + equation_of_equinoxes).rem_euclid(2.0 * PI)
+}
+
+// Angle between the mean equator of date and the ecliptic, in radians.
+pub fn mean_obliquity(timestamp: &DateTime<Utc>) -> f64 {
+    let t = julian_centuries(timestamp);
+    J2000_OBLIQUITY + (-46.8150 * t - 0.00059 * t * t + 0.001813 * t * t * t) * ARCSEC
+}
+
+// Nutation in longitude and in obliquity, in radians.
+#[derive(Copy, Clone, Debug)]
+pub struct Nutation {
+    pub longitude: f64,
+    pub obliquity: f64,
+}
+
+// Returns the nutation, using the terms larger than 0.1 arcsecond (the result
+// is accurate to about 0.5 arcsecond).
+pub fn nutation(timestamp: &DateTime<Utc>) -> Nutation {
+    let t = julian_centuries(timestamp);
+    // Longitude of the ascending node of the Moon's orbit, and mean longitudes
+    // of the Sun and the Moon.
+    let node = (125.04452 - 1934.136261 * t).to_radians();
+    let sun = (280.4665 + 36000.7698 * t).to_radians();
+    let moon = (218.3165 + 481267.8813 * t).to_radians();
+    Nutation {
+        longitude: (-17.20 * node.sin() - 1.32 * (2.0 * sun).sin() - 0.23 * (2.0 * moon).sin()
+            + 0.21 * (2.0 * node).sin())
+            * ARCSEC,
+        obliquity: (9.20 * node.cos() + 0.57 * (2.0 * sun).cos() + 0.10 * (2.0 * moon).cos()
+            - 0.09 * (2.0 * node).cos())
+            * ARCSEC,
+    }
+}
+
+// Returns the rotation from the mean equator and equinox of date to the
+// equator and equinox of J2000.
+fn precession(timestamp: &DateTime<Utc>) -> UnitQuaternion<f64> {
+    let t = julian_centuries(timestamp);
+    let zeta = (2306.2181 * t + 0.30188 * t * t + 0.017998 * t * t * t) * ARCSEC;
+    let z = (2306.2181 * t + 1.09468 * t * t + 0.018203 * t * t * t) * ARCSEC;
+    let theta = (2004.3109 * t - 0.42665 * t * t - 0.041833 * t * t * t) * ARCSEC;
+    UnitQuaternion::from_axis_angle(&Vector3::z_axis(), -zeta)
+        * UnitQuaternion::from_axis_angle(&Vector3::y_axis(), theta)
+        * UnitQuaternion::from_axis_angle(&Vector3::z_axis(), -z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geo::LatLon, time::parse_utc};
+
+    // Converts an angle to seconds of time.
+    fn time_seconds(angle: f64) -> f64 {
+        angle / (2.0 * PI) * 86400.0
+    }
+
+    fn hms(h: f64, m: f64, s: f64) -> f64 {
+        (h * 60.0 + m) * 60.0 + s
+    }
+
+    #[test]
+    fn sidereal_time() {
+        // Meeus, examples 12.a and 12.b.
+        let t = parse_utc("1987-04-10 00:00:00").unwrap();
+        let gmst = time_seconds(greenwich_mean_sidereal_time(&t));
+        assert!((gmst - hms(13.0, 10.0, 46.3668)).abs() < 0.01, "{}", gmst);
+        let gast = time_seconds(greenwich_apparent_sidereal_time(&t));
+        assert!((gast - hms(13.0, 10.0, 46.1351)).abs() < 0.05, "{}", gast);
+
+        let t = parse_utc("1987-04-10 19:21:00").unwrap();
+        let gmst = time_seconds(greenwich_mean_sidereal_time(&t));
+        assert!((gmst - hms(8.0, 34.0, 57.0896)).abs() < 0.01, "{}", gmst);
+    }
+
+    #[test]
+    fn obliquity_and_nutation() {
+        // Meeus, example 22.a.
+        let t = parse_utc("1987-04-10 00:00:00").unwrap();
+        let obliquity = mean_obliquity(&t) / ARCSEC;
+        assert!(
+            (obliquity - hms(23.0, 26.0, 27.407)).abs() < 0.01,
+            "{}",
+            obliquity
+        );
+        let nutation = nutation(&t);
+        assert!((nutation.longitude / ARCSEC + 3.788).abs() < 0.5);
+        assert!((nutation.obliquity / ARCSEC - 9.443).abs() < 0.5);
+    }
+
+    #[test]
+    fn precession_of_equinox() {
+        // The equinox moves west along the ecliptic by 5029.0966 arcseconds
+        // per century.
+        let t = parse_utc("2100-01-01 12:00:00").unwrap();
+        let equinox = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), -J2000_OBLIQUITY)
+            * precession(&t)
+            * Vector3::x();
+        let longitude = f64::atan2(equinox.y, equinox.x) / ARCSEC;
+        assert!((longitude + 5029.0966).abs() < 5.0, "{}", longitude);
+        assert!(equinox.z.abs() < 10.0 * ARCSEC);
+    }
+
+    #[test]
+    fn solstice() {
+        // At the June solstice of 2000, the Sun is above the tropic of Cancer.
+        // The equation of time is -1.7 minutes, so it is 0.4 degrees east of
+        // where it would be for the UTC time.
+        let t = parse_utc("2000-06-21 01:47:43").unwrap();
+        let sun = earth_orientation(&t).inverse() * Vector3::y();
+        let subsolar = LatLon::from_earth_fixed(&sun);
+        assert!((subsolar.lat - 23.44).abs() < 0.01, "{}", subsolar);
+        let noon = (12.0 - hms(1.0, 47.0, 43.0) / 3600.0) * 15.0;
+        assert!((subsolar.lon - (noon + 0.43)).abs() < 0.1, "{}", subsolar);
+
+        // The north pole is tilted away from the ecliptic pole.
+        let pole = earth_orientation(&t) * Vector3::z();
+        assert!((pole.angle(&Vector3::z()).to_degrees() - 23.44).abs() < 0.01);
+    }
+}
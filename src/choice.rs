@@ -1,9 +1,9 @@
 use std::{ops::Deref, rc::Rc};
 
 #[derive(Clone, PartialEq, Eq)]
-pub struct ChoiceSet<T: Copy>(Rc<Vec<T>>);
+pub struct ChoiceSet<T: Clone>(Rc<Vec<T>>);
 
-impl<T: Copy> ChoiceSet<T> {
+impl<T: Clone> ChoiceSet<T> {
     pub fn new<I>(vals: I) -> Self
     where
         I: IntoIterator<Item = T>,
@@ -19,7 +19,6 @@ impl<T: Copy> ChoiceSet<T> {
         }
     }
 
-    #[allow(dead_code)]
     pub fn by_value(&self, value: T) -> Choice<T>
     where
         T: PartialEq,
@@ -33,7 +32,7 @@ impl<T: Copy> ChoiceSet<T> {
     }
 }
 
-impl<T: Copy> Deref for ChoiceSet<T> {
+impl<T: Clone> Deref for ChoiceSet<T> {
     type Target = Vec<T>;
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -41,14 +40,14 @@ impl<T: Copy> Deref for ChoiceSet<T> {
 }
 
 #[derive(Clone, PartialEq, Eq)]
-pub struct Choice<T: Copy> {
+pub struct Choice<T: Clone> {
     choice_set: ChoiceSet<T>,
     index: usize,
 }
 
-impl<T: Copy> Choice<T> {
+impl<T: Clone> Choice<T> {
     pub fn get(&self) -> T {
-        self.choice_set[self.index]
+        self.choice_set[self.index].clone()
     }
 
     pub fn index(&self) -> usize {
@@ -91,7 +90,7 @@ impl<T: Copy> Choice<T> {
     }
 }
 
-impl<T: Copy> Deref for Choice<T> {
+impl<T: Clone> Deref for Choice<T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         &self.choice_set[self.index]
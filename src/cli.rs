@@ -0,0 +1,615 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    body::Body,
+    config::{horizons, to_toml, CameraSpec, Config, Preset, Session},
+    eclipse,
+    frames::FrameSequence,
+    geo::LatLon,
+    lunar,
+    simulation::{self, IntegratorKind, Seconds, Snapshot},
+    time::{self, julian_date, parse_utc, TimeScale},
+};
+
+const USAGE: &str = "\
+Usage:
+  planets-rs                          Start the visualizer.
+  planets-rs propagate [options] TIME Integrate a preset to the given UTC time
+                                      and print the Earth and Moon state vectors.
+  planets-rs eclipses [options] TIME  List the solar and lunar eclipses between
+                                      the preset and the given UTC time.
+  planets-rs phases [options] TIME    List the principal phases of the Moon
+                                      between the preset and the given UTC time.
+  planets-rs orbit [options] TIME     List the perigees, apogees and node
+                                      crossings of the Moon between the preset
+                                      and the given UTC time.
+  planets-rs import [--name NAME] FILE...
+                                      Convert saved JPL Horizons vector tables
+                                      (one for each body) to a preset file.
+  planets-rs frames [options] START END
+                                      Render the simulation between two UTC
+                                      times to numbered PNG files, at a fixed
+                                      simulated interval per frame. This
+                                      opens a window while rendering.
+
+Options:
+  --preset NAME|INDEX|FILE Preset to start from (default: the first preset).
+  --integrator NAME        Integration method (Verlet, RK4, Yoshida, RKF45).
+  --step SECONDS           Maximum integration step (default: 60).
+  --path                   With eclipses, also print the ground track of
+                           central solar eclipses.
+  --observer LAT,LON       With eclipses, also print the local circumstances of
+                           solar eclipses at the given location (in degrees,
+                           positive north and east).
+  --name NAME              Name of the imported preset.
+  --interval SECONDS       With frames, simulated time between frames
+                           (default: 60).
+  --camera NAME            With frames, the camera (e.g. Earth, Moon).
+  --session FILE           With frames, take the camera, view, display options
+                           and initial state from a saved session.
+  --size WIDTHxHEIGHT      With frames, the image size (default: 1280x720).
+  --out DIR                With frames, the output directory (default:
+                           ./frames).
+  --ui                     With frames, include the UI panel.
+
+TIME is a UTC time like \"2017-08-21 18:25:00\" or \"2017-08-21T18:25:00Z\".
+";
+
+// Runs a command-line (headless) command. No window or GL context is created,
+// except by frames. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let result = match args[0].as_str() {
+        "propagate" => propagate(&args[1..]),
+        "eclipses" => eclipses(&args[1..]),
+        "phases" => phases(&args[1..]),
+        "orbit" => orbit(&args[1..]),
+        "import" => import(&args[1..]),
+        "frames" => parse_frames(&args[1..]).and_then(|f| f.render()),
+        "help" | "-h" | "--help" => {
+            print!("{}", USAGE);
+            return 0;
+        }
+        cmd => Err(format!("unknown command '{}'", cmd)),
+    };
+    match result {
+        Ok(output) => {
+            print!("{}", output);
+            0
+        }
+        Err(err) => {
+            eprintln!("Error: {}\n\n{}", err, USAGE);
+            2
+        }
+    }
+}
+
+// Options shared by the commands that run the simulation.
+struct SimOptions {
+    preset: Preset,
+    integrator: IntegratorKind,
+    step: f64,
+    target: DateTime<Utc>,
+    path: bool,
+    observer: Option<LatLon>,
+}
+
+impl SimOptions {
+    // Parses the options of the given command; options that only apply to
+    // other commands are rejected.
+    fn parse(command: &str, args: &[String]) -> Result<Self, String> {
+        let config = Config::load(Path::new(Config::PRESETS_DIR))?;
+        let mut preset = config.initial_preset.get();
+        let mut integrator = config.initial_integrator.get();
+        let mut step = 60.0;
+        let mut target: Option<DateTime<Utc>> = None;
+        let mut path = false;
+        let mut observer = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--preset" => preset = find_preset(&config, value()?)?,
+                "--integrator" => integrator = find_integrator(value()?)?,
+                "--step" => {
+                    step = value()?
+                        .parse()
+                        .ok()
+                        .filter(|&s: &f64| s.is_finite() && s > 0.0)
+                        .ok_or("invalid step")?;
+                }
+                "--path" | "--observer" if command != "eclipses" => {
+                    return Err(format!("{} only applies to eclipses", arg))
+                }
+                "--path" => path = true,
+                "--observer" => observer = Some(LatLon::parse(value()?)?),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ if target.is_none() => target = Some(parse_utc(arg)?),
+                _ => return Err(format!("unexpected argument '{}'", arg)),
+            }
+        }
+        Ok(Self {
+            preset,
+            integrator,
+            step,
+            target: target.ok_or("missing target time")?,
+            path,
+            observer,
+        })
+    }
+
+    fn header(&self) -> String {
+        format!(
+            "Preset: {}\nIntegrator: {} (max step {}s)\n",
+            self.preset.name,
+            self.integrator.name(),
+            self.step
+        )
+    }
+}
+
+fn propagate(args: &[String]) -> Result<String, String> {
+    let opts = SimOptions::parse("propagate", args)?;
+    let snapshot = simulation::propagate(
+        &opts.preset.snapshot,
+        opts.integrator,
+        opts.target,
+        Seconds(opts.step),
+    );
+
+    let mut out = opts.header();
+    let _ = write!(out, "{}", state_vectors(&snapshot));
+    Ok(out)
+}
+
+fn eclipses(args: &[String]) -> Result<String, String> {
+    let opts = SimOptions::parse("eclipses", args)?;
+    let eclipses = eclipse::find_eclipses(
+        &opts.preset.snapshot,
+        opts.integrator,
+        opts.target,
+        Seconds(opts.step),
+    );
+
+    let mut out = opts.header();
+    let _ = writeln!(
+        out,
+        "{:<23} {:<17} {:>9} {:>8}",
+        "Greatest eclipse", "Type", "Magnitude", "Gamma"
+    );
+    for e in eclipses {
+        let _ = writeln!(
+            out,
+            "{:<23} {:<17} {:>9.4} {:>8.4}",
+            e.greatest.format("%Y-%m-%d %H:%M:%S UTC"),
+            e.description(),
+            e.magnitude,
+            e.gamma
+        );
+        for c in &e.contacts {
+            let _ = writeln!(
+                out,
+                "  {:<21} {} {}",
+                c.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                c.contact.name(),
+                c.contact.description(e.kind)
+            );
+        }
+        if opts.path && !e.path.is_empty() {
+            let _ = write!(out, "{}", eclipse_path(&e.path));
+        }
+        if let Some(observer) = opts.observer {
+            let local =
+                eclipse::local_circumstances(&e, observer, opts.integrator, Seconds(opts.step));
+            if let Some(local) = local {
+                let _ = write!(out, "{}", local_eclipse(&local));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn phases(args: &[String]) -> Result<String, String> {
+    let opts = SimOptions::parse("phases", args)?;
+    let phases = lunar::find_phases(
+        &opts.preset.snapshot,
+        opts.integrator,
+        opts.target,
+        Seconds(opts.step),
+    );
+
+    let mut out = opts.header();
+    let _ = writeln!(out, "{:<23} Phase", "Time");
+    for p in phases {
+        let _ = writeln!(
+            out,
+            "{:<23} {}",
+            p.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            p.name.name()
+        );
+    }
+    Ok(out)
+}
+
+fn orbit(args: &[String]) -> Result<String, String> {
+    let opts = SimOptions::parse("orbit", args)?;
+    let events = lunar::find_orbit_events(
+        &opts.preset.snapshot,
+        opts.integrator,
+        opts.target,
+        Seconds(opts.step),
+    );
+
+    let mut out = opts.header();
+    let _ = writeln!(out, "{:<23} {:<15} {:>10}", "Time", "Event", "Distance");
+    for e in events {
+        let _ = writeln!(
+            out,
+            "{:<23} {:<15} {:>7.0} km",
+            e.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            e.kind.name(),
+            e.distance
+        );
+    }
+    Ok(out)
+}
+
+fn eclipse_path(path: &[eclipse::PathPoint]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "  {:<8} {:<16} {:<16} {:<16} {:>5} {}",
+        "Time", "Central line", "Northern limit", "Southern limit", "Width", "Type"
+    );
+    let limit = |l: Option<LatLon>| l.map_or("-".to_string(), |l| l.to_string());
+    for p in path {
+        let _ = writeln!(
+            out,
+            "  {:<8} {:<16} {:<16} {:<16} {:>5} {}",
+            p.timestamp.format("%H:%M:%S"),
+            p.central.to_string(),
+            limit(p.north),
+            limit(p.south),
+            p.width().map_or("-".to_string(), |w| format!("{:.0}", w)),
+            if p.total { "total" } else { "annular" }
+        );
+    }
+    out
+}
+
+fn local_eclipse(local: &eclipse::LocalEclipse) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "  Seen from {}: {} eclipse, magnitude {:.4}, obscuration {:.1}%{}",
+        local.observer,
+        local.eclipse_type.name().to_lowercase(),
+        local.magnitude,
+        local.obscuration * 100.0,
+        if local.is_visible() {
+            ""
+        } else {
+            " (Sun below the horizon)"
+        }
+    );
+    let mut events = vec![
+        (local.start, "Eclipse begins", Some(local.sun_at_start)),
+        (local.maximum, "Maximum", Some(local.sun_at_maximum)),
+        (local.end, "Eclipse ends", Some(local.sun_at_end)),
+    ];
+    if let Some((begin, end)) = local.central {
+        let names = match local.eclipse_type {
+            eclipse::EclipseType::Annular => ("Annularity begins", "Annularity ends"),
+            _ => ("Totality begins", "Totality ends"),
+        };
+        events.push((begin, names.0, None));
+        events.push((end, names.1, None));
+        events.sort_by_key(|e| e.0);
+    }
+    for (timestamp, name, sun) in events {
+        let _ = writeln!(
+            out,
+            "  {:<21} {:<18} {}",
+            timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+            name,
+            sun.map_or(String::new(), |h| format!("Sun {}", h))
+        );
+    }
+    out
+}
+
+fn parse_frames(args: &[String]) -> Result<FrameSequence, String> {
+    let config = Config::load(Path::new(Config::PRESETS_DIR))?;
+    let mut preset = config.initial_preset.get();
+    let mut session = None;
+    let mut camera = None;
+    let mut observer = None;
+    let mut integrator = None;
+    let mut times = Vec::new();
+    let mut interval = 60.0;
+    let mut step = 60.0;
+    let mut size = (1280, 720);
+    let mut out_dir = PathBuf::from("./frames");
+    let mut show_ui = false;
+
+    let positive = |s: &str, name: &str| {
+        s.parse()
+            .ok()
+            .filter(|&x: &f64| x.is_finite() && x > 0.0)
+            .ok_or_else(|| format!("invalid {}", name))
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--preset" => preset = find_preset(&config, value()?)?,
+            "--integrator" => integrator = Some(find_integrator(value()?)?),
+            "--step" => step = positive(value()?, "step")?,
+            "--interval" => interval = positive(value()?, "interval")?,
+            "--camera" => {
+                let name = value()?;
+                camera = Some(
+                    CameraSpec::by_description(name)
+                        .ok_or_else(|| format!("unknown camera '{}'", name))?,
+                );
+            }
+            "--observer" => observer = Some(LatLon::parse(value()?)?),
+            "--session" => session = Some(Session::load(Path::new(value()?))?),
+            "--size" => size = parse_size(value()?)?,
+            "--out" => out_dir = PathBuf::from(value()?),
+            "--ui" => show_ui = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if times.len() < 2 => times.push(parse_utc(arg)?),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+    let (start, end) = match times[..] {
+        [start, end] if start <= end => (start, end),
+        [_, _] => return Err("the end time is before the start time".to_string()),
+        _ => return Err("missing start or end time".to_string()),
+    };
+    Ok(FrameSequence {
+        preset,
+        session,
+        camera,
+        observer,
+        integrator,
+        start,
+        end,
+        interval: Seconds(interval),
+        max_step: Seconds(step),
+        width: size.0,
+        height: size.1,
+        out_dir,
+        show_ui,
+    })
+}
+
+// Parses an image size like "1280x720".
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    s.split_once('x')
+        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .filter(|&(w, h)| w > 0 && h > 0)
+        .ok_or_else(|| format!("invalid size '{}'", s))
+}
+
+fn import(args: &[String]) -> Result<String, String> {
+    let mut name = None;
+    let mut text = String::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--name" => name = Some(args.next().ok_or("missing value for --name")?.clone()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            file => {
+                let contents = fs::read_to_string(file).map_err(|e| format!("{}: {}", file, e))?;
+                text.push_str(&contents);
+                text.push('\n');
+            }
+        }
+    }
+    if text.is_empty() {
+        return Err("no Horizons files".to_string());
+    }
+
+    let vectors = horizons::parse(&text)?;
+    let snapshot = horizons::to_snapshot(&vectors)?;
+    let name = name.unwrap_or_else(|| snapshot.timestamp.format("%Y-%m-%d").to_string());
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# Imported from JPL Horizons.");
+    let _ = writeln!(out, "# Coordinate center: {}", vectors[0].center);
+    let _ = writeln!(out);
+    out.push_str(&to_toml(&name, &snapshot));
+    Ok(out)
+}
+
+// Formats the Earth and Moon state vectors relative to the Sun, similar to a
+// JPL Horizons vector table (km and km/s).
+pub fn state_vectors(snapshot: &Snapshot) -> String {
+    let mut out = String::new();
+    // Horizons uses TDB by default.
+    for scale in [TimeScale::Utc, TimeScale::Tdb] {
+        let _ = writeln!(
+            out,
+            "{:.9} = A.D. {} {}",
+            julian_date(&snapshot.timestamp) + scale.offset(&snapshot.timestamp) / 86400.0,
+            time::to_scale(&snapshot.timestamp, scale).format("%Y-%b-%d %H:%M:%S%.3f"),
+            scale.name(),
+        );
+    }
+    let sun = snapshot.state(Body::Sun);
+    for body in [Body::Earth, Body::Moon] {
+        let s = snapshot.state(body);
+        let p = s.position - sun.position;
+        let v = s.velocity - sun.velocity;
+        let _ = writeln!(out, "{}:", body.props().name);
+        let _ = writeln!(
+            out,
+            " X ={} Y ={} Z ={}",
+            horizons_float(p.x),
+            horizons_float(p.y),
+            horizons_float(p.z)
+        );
+        let _ = writeln!(
+            out,
+            " VX={} VY={} VZ={}",
+            horizons_float(v.x),
+            horizons_float(v.y),
+            horizons_float(v.z)
+        );
+    }
+    out
+}
+
+fn find_preset(config: &Config, name: &str) -> Result<Preset, String> {
+    let presets = config.initial_preset.choice_set();
+    if let Ok(idx) = name.parse::<usize>() {
+        if idx >= 1 && idx <= presets.len() {
+            return Ok(presets[idx - 1].clone());
+        }
+    }
+    if let Some(p) = presets.iter().find(|p| p.name.eq_ignore_ascii_case(name)) {
+        return Ok(p.clone());
+    }
+    let path = Path::new(name);
+    if path.is_file() {
+        return Preset::load(path);
+    }
+    Err(format!("unknown preset '{}'", name))
+}
+
+fn find_integrator(name: &str) -> Result<IntegratorKind, String> {
+    IntegratorKind::from_name(name).ok_or_else(|| format!("unknown integrator '{}'", name))
+}
+
+// Formats a number like Horizons does, e.g. " 1.290745457486534E+08".
+fn horizons_float(x: f64) -> String {
+    let s = format!("{:.15E}", x);
+    let (mantissa, exp) = s.split_once('E').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    let sign = if x.is_sign_negative() { "" } else { " " };
+    format!(
+        "{}{}E{}{:02}",
+        sign,
+        mantissa,
+        if exp < 0 { '-' } else { '+' },
+        exp.abs()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_horizons_float() {
+        let tests = [
+            (1.290745457486534E+08, " 1.290745457486534E+08"),
+            (-7.899200932997707E+07, "-7.899200932997707E+07"),
+            (-2.302676624889699E-03, "-2.302676624889699E-03"),
+            (0.0, " 0.000000000000000E+00"),
+        ];
+        for (x, expected) in tests {
+            assert_eq!(horizons_float(x), expected);
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let args: Vec<String> = ["--integrator", "yoshida", "2017-08-22 00:00"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let out = propagate(&args).unwrap();
+        assert_eq!(out, propagate(&args).unwrap());
+        assert!(out.contains("A.D. 2017-Aug-22 00:00:00.000 UTC"));
+        assert!(out.contains("A.D. 2017-Aug-22 00:01:09.182 TDB"), "{}", out);
+
+        for step in ["0", "-60", "inf", "NaN"] {
+            let args: Vec<String> = ["--step", step, "2017-08-22 00:00"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+            assert_eq!(propagate(&args).unwrap_err(), "invalid step");
+        }
+
+        for option in [&["--path"][..], &["--observer", "40.71,-74.01"]] {
+            let args: Vec<String> = option
+                .iter()
+                .chain(&["2017-08-22 00:00"])
+                .map(|s| s.to_string())
+                .collect();
+            let err = propagate(&args).unwrap_err();
+            assert_eq!(err, format!("{} only applies to eclipses", option[0]));
+        }
+    }
+
+    #[test]
+    fn frames_options() {
+        let args = |a: &[&str]| -> Vec<String> { a.iter().map(|s| s.to_string()).collect() };
+        let f = parse_frames(&args(&[
+            "--camera",
+            "moon",
+            "--interval",
+            "30",
+            "--size",
+            "640x360",
+            "2017-08-21 17:00",
+            "2017-08-21 20:00",
+        ]))
+        .unwrap();
+        assert!(f.camera == Some(CameraSpec::ALL[1]));
+        assert_eq!(f.interval.0, 30.0);
+        assert_eq!((f.width, f.height), (640, 360));
+        assert!(!f.show_ui);
+
+        for (a, expected) in [
+            (&["2017-08-21 17:00"][..], "missing start or end time"),
+            (
+                &["2017-08-21 17:00", "2017-08-21 16:00"],
+                "end time is before",
+            ),
+            (&["--size", "640", "2017-08-21 17:00"], "invalid size '640'"),
+            (&["--interval", "-1"], "invalid interval"),
+            (&["--interval", "inf"], "invalid interval"),
+            (&["--camera", "Mars"], "unknown camera 'Mars'"),
+        ] {
+            let err = parse_frames(&args(a)).err().unwrap();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn import_horizons() {
+        let args: Vec<String> = [
+            "--name",
+            "Eclipse",
+            "src/config/testdata/horizons/earth-sun-text.txt",
+            "src/config/testdata/horizons/moon-sun-csv.txt",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let out = import(&args).unwrap();
+        assert!(out.contains("# Coordinate center: Sun (10)"), "{}", out);
+        assert!(out.contains("name = \"Eclipse\""), "{}", out);
+        assert!(out.contains("[bodies.Moon]"), "{}", out);
+
+        let err = import(&args[..3]).unwrap_err();
+        assert!(err.contains("missing vectors for Moon"), "{}", err);
+    }
+}
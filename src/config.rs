@@ -1,22 +1,65 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
     body::Body,
     choice::{Choice, ChoiceSet},
-    simulation::Snapshot,
+    geo::{Horizontal, LatLon},
+    simulation::{IntegratorKind, Snapshot},
 };
 
+pub mod horizons;
+mod presets;
+mod session;
+
+pub use presets::to_toml;
+pub use session::Session;
+
 pub struct Config {
     pub initial_preset: Choice<Preset>,
     pub initial_camera: Choice<CameraSpec>,
-    pub initial_speed: Choice<chrono::Duration>,
+    pub initial_speed: chrono::Duration,
+    pub initial_integrator: Choice<IntegratorKind>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Preset {
-    pub name: &'static str,
+    pub name: String,
     pub snapshot: Snapshot,
+    // Integrator to switch to when the preset is loaded (if set).
+    pub integrator: Option<IntegratorKind>,
+    // Camera to switch to when the preset is loaded (if set).
+    pub camera: Option<CameraSpec>,
+    // Location and view of the observer camera (if set).
+    pub observer: Option<Observer>,
+    // Length of the timeline shown in the UI, which starts at the snapshot
+    // time.
+    pub span: chrono::Duration,
+}
+
+impl Preset {
+    pub const DEFAULT_SPAN_DAYS: i64 = 365;
+    // The eclipses of the whole span are searched when the preset is loaded.
+    pub const MAX_SPAN_DAYS: f64 = 3650.0;
+
+    // Loads a preset from a TOML or JSON file. See presets/README.md for the
+    // format.
+    pub fn load(path: &Path) -> Result<Preset, String> {
+        presets::load_file(path)
+    }
+}
+
+// Returns the snapshot of a preset file in the presets directory. Used by
+// tests.
+#[cfg(test)]
+pub fn load_preset(file: &str) -> Snapshot {
+    Preset::load(&Path::new(Config::PRESETS_DIR).join(file))
+        .unwrap()
+        .snapshot
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct CameraSpec {
     pub focus: Body,
     pub direction: CameraDirection,
@@ -25,77 +68,150 @@ pub struct CameraSpec {
     pub description: &'static str,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum CameraDirection {
     FromAbove,
     FromBody(Body),
+    // The eye stands on the Earth's surface; see Observer.
+    FromSurface,
+}
+
+// Settings for the camera that stands on the Earth's surface.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Observer {
+    pub location: LatLon,
+    pub look_at: LookAt,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LookAt {
+    Body(Body),
+    Sky(Horizontal),
+}
+
+impl Default for Observer {
+    // Greenwich, looking at the Sun.
+    fn default() -> Self {
+        Self {
+            location: LatLon::new(51.48, 0.0),
+            look_at: LookAt::Body(Body::Sun),
+        }
+    }
+}
+
+// The part of the camera view that is controlled with the mouse, on top of the
+// camera spec.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CameraView {
+    // Distance from the focus, in km.
+    pub distance: f64,
+    // Rotations added by the user, in radians.
+    pub pitch: f64,
+    pub yaw: f64,
+    // Vertical field of view, in radians.
+    pub fov: f64,
+}
+
+impl CameraView {
+    // Field of view of the camera, except when the observer camera zooms in.
+    pub const DEFAULT_FOV: f64 = std::f64::consts::FRAC_PI_4;
+}
+
+// Visibility of the optional scene elements.
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RenderToggles {
+    pub trails: bool,
+    pub ecliptic: bool,
+    pub skybox: bool,
+    pub earth_axis: bool,
+    pub eclipse_path: bool,
+    // Markers for the apsides and nodes of the Moon (missing in older
+    // sessions).
+    #[serde(default)]
+    pub orbit_markers: bool,
+}
+
+impl CameraSpec {
+    pub const ALL: [CameraSpec; 5] = [
+        CameraSpec {
+            focus: Body::Earth,
+            direction: CameraDirection::FromAbove,
+            relative_dist: 10.0,
+            description: "Earth",
+        },
+        CameraSpec {
+            focus: Body::Moon,
+            direction: CameraDirection::FromAbove,
+            relative_dist: 30.0,
+            description: "Moon",
+        },
+        CameraSpec {
+            focus: Body::Moon,
+            direction: CameraDirection::FromBody(Body::Earth),
+            relative_dist: 10.0,
+            description: "Moon phase",
+        },
+        CameraSpec {
+            focus: Body::Sun,
+            direction: CameraDirection::FromAbove,
+            relative_dist: 100.0,
+            description: "Sun",
+        },
+        CameraSpec {
+            focus: Body::Earth,
+            direction: CameraDirection::FromSurface,
+            relative_dist: 1.0,
+            description: "Observer",
+        },
+    ];
+
+    // The view when the camera is selected, before the user moves it.
+    pub fn default_view(&self) -> CameraView {
+        CameraView {
+            distance: self.relative_dist * self.focus.radius64(),
+            pitch: 0.0,
+            yaw: 0.0,
+            fov: CameraView::DEFAULT_FOV,
+        }
+    }
+
+    pub fn by_description(description: &str) -> Option<CameraSpec> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.description.eq_ignore_ascii_case(description))
+    }
 }
 
 impl Config {
-    pub fn default() -> Self {
-        let presets = [
-            Preset {
-                name: "Solar eclipse Aug 2017",
-                snapshot: Snapshot::solar_eclipse_aug_2017(),
-            },
-            Preset {
-                name: "Lunar eclipse May 2022",
-                snapshot: Snapshot::lunar_eclipse_may_2022(),
-            },
-            // Preset {
-            //     name: "test - no moon inclination",
-            //     snapshot: Snapshot::test_no_moon_inclination(),
-            // },
-            // Preset {
-            //     name: "test - high moon inclination",
-            //     snapshot: Snapshot::test_high_moon_inclination(),
-            // },
-        ];
+    pub const PRESETS_DIR: &'static str = "./presets";
+    // Where the session is saved (F5) and loaded from (F9).
+    pub const SESSION_FILE: &'static str = "./session.toml";
+    // Where screenshots are saved (F12, or F11 without the UI).
+    pub const SCREENSHOT_DIR: &'static str = "./screenshots";
+
+    // Creates the configuration, loading the presets from the given
+    // directory.
+    pub fn load(presets_dir: &Path) -> Result<Self, String> {
+        let presets = presets::load_dir(presets_dir)?;
+        if presets.is_empty() {
+            return Err(format!("no presets found in {}", presets_dir.display()));
+        }
         let initial_preset = ChoiceSet::new(presets).by_index(0);
 
-        let camera_specs = [
-            CameraSpec {
-                focus: Body::Earth,
-                direction: CameraDirection::FromAbove,
-                relative_dist: 10.0,
-                description: "Earth",
-            },
-            CameraSpec {
-                focus: Body::Moon,
-                direction: CameraDirection::FromAbove,
-                relative_dist: 30.0,
-                description: "Moon",
-            },
-            CameraSpec {
-                focus: Body::Moon,
-                direction: CameraDirection::FromBody(Body::Earth),
-                relative_dist: 10.0,
-                description: "Moon phase",
-            },
-            CameraSpec {
-                focus: Body::Sun,
-                direction: CameraDirection::FromAbove,
-                relative_dist: 100.0,
-                description: "Sun",
-            },
-        ];
-        let initial_camera = ChoiceSet::new(camera_specs).by_index(0);
-
-        let speeds = [
-            chrono::Duration::minutes(1),
-            chrono::Duration::minutes(15),
-            chrono::Duration::hours(1),
-            chrono::Duration::hours(4),
-            chrono::Duration::days(1),
-            chrono::Duration::days(5),
-            chrono::Duration::days(30),
-            chrono::Duration::days(90),
-        ];
-        let initial_speed = ChoiceSet::new(speeds).by_index(2);
-        Self {
+        let mut initial_camera = ChoiceSet::new(CameraSpec::ALL).by_index(0);
+        if let Some(camera) = initial_preset.camera {
+            initial_camera = initial_camera.choice_set().by_value(camera);
+        }
+
+        let initial_speed = chrono::Duration::hours(1);
+
+        let initial_integrator = ChoiceSet::new(IntegratorKind::ALL).by_index(0);
+        Ok(Self {
             initial_preset,
             initial_camera,
             initial_speed,
-        }
+            initial_integrator,
+        })
     }
 }
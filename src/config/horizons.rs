@@ -0,0 +1,409 @@
+// Parser for JPL Horizons vector tables (https://ssd.jpl.nasa.gov/horizons/).
+//
+// Supported layouts:
+//  - the default text layout, with or without labels (VEC_LABELS);
+//  - CSV (CSV_FORMAT=YES);
+//  - JSON responses from the Horizons API, which wrap the text output.
+//
+// Only cartesian state vectors (VEC_TABLE=2 or 3) in ecliptic coordinates
+// (REF_PLANE=ECLIPTIC), relative to a body center, are supported.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeZone, Utc};
+use kiss3d::nalgebra::{Point3, Vector3};
+
+use crate::{
+    body::Body,
+    simulation::{BodyState, Snapshot},
+    time::{self, TimeScale},
+};
+
+const AU: f64 = 149597870.700; // km
+const DAY: f64 = 86400.0; // s
+
+// The state vector of a body, from one Horizons vector table.
+#[derive(Clone, Debug)]
+pub struct Vectors {
+    pub target: Body,
+    // Center body name, e.g. "Sun (10)".
+    pub center: String,
+    // The center, if it is one of the simulated bodies.
+    pub center_body: Option<Body>,
+    pub timestamp: DateTime<Utc>,
+    // Position and velocity relative to the center, in km and km/s.
+    pub state: BodyState,
+}
+
+// Parses the text of a Horizons response. The text can contain multiple
+// concatenated responses (e.g. one for each body). Only the first entry of
+// each table is used.
+pub fn parse(text: &str) -> Result<Vec<Vectors>, String> {
+    let text = unwrap_json(text)?;
+    let mut result = Vec::new();
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find("$$SOE") {
+        let header = &rest[..start];
+        let end = rest[start..]
+            .find("$$EOE")
+            .map(|i| start + i)
+            .ok_or("missing $$EOE")?;
+        let table = &rest[start + "$$SOE".len()..end];
+        let n = result.len() + 1;
+        result.push(parse_table(header, table).map_err(|e| format!("table {}: {}", n, e))?);
+        rest = &rest[end..];
+    }
+    if result.is_empty() {
+        return Err("no vector table found (missing $$SOE)".to_string());
+    }
+    Ok(result)
+}
+
+// Combines the vectors of multiple bodies into a snapshot. All vectors must
+// have the same center and timestamp. The center must be one of the simulated
+// bodies, unless vectors are provided for all bodies.
+//
+// The returned snapshot uses the frame of the center; it is not barycentric.
+pub fn to_snapshot(vectors: &[Vectors]) -> Result<Snapshot, String> {
+    let first = vectors.first().ok_or("no vectors")?;
+    let mut states: HashMap<usize, (Body, BodyState)> = HashMap::new();
+    for v in vectors {
+        if v.center != first.center {
+            return Err(format!(
+                "{:?} vectors are relative to {}, expected {}",
+                v.target, v.center, first.center
+            ));
+        }
+        if (v.timestamp - first.timestamp).num_milliseconds() != 0 {
+            return Err(format!(
+                "{:?} vectors are for {}, expected {}",
+                v.target, v.timestamp, first.timestamp
+            ));
+        }
+        if states
+            .insert(v.target.index(), (v.target, v.state))
+            .is_some()
+        {
+            return Err(format!("multiple vectors for {:?}", v.target));
+        }
+    }
+    for body in Body::ALL {
+        if !states.contains_key(&body.index()) && first.center_body != Some(body) {
+            return Err(format!(
+                "missing vectors for {:?} (relative to {})",
+                body, first.center
+            ));
+        }
+    }
+    Ok(Snapshot::new(first.timestamp, states.into_values()))
+}
+
+// Horizons API responses are JSON objects with the text output in "result".
+fn unwrap_json(text: &str) -> Result<String, String> {
+    if !text.trim_start().starts_with('{') {
+        return Ok(text.to_string());
+    }
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    if let Some(err) = value["error"].as_str() {
+        return Err(format!("Horizons error: {}", err.trim()));
+    }
+    value["result"]
+        .as_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| "JSON response has no result".to_string())
+}
+
+fn parse_table(header: &str, table: &str) -> Result<Vectors, String> {
+    // Header lines are of the form "Key name : value {comment}".
+    let mut fields = HashMap::new();
+    for line in header.lines() {
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.split('{').next().unwrap_or_default();
+            fields.insert(key.trim(), value.trim());
+        }
+    }
+    let field = |key: &str| {
+        fields
+            .get(key)
+            .copied()
+            .ok_or_else(|| format!("missing '{}' in header", key))
+    };
+
+    let target_name = field("Target body name")?;
+    let target = horizons_body(target_name)
+        .ok_or_else(|| format!("unsupported target body '{}'", target_name))?;
+    let center = field("Center body name")?;
+    let site = field("Center-site name")?;
+    if site != "BODY CENTER" {
+        return Err(format!(
+            "unsupported center site '{}' (use a body center, e.g. @sun)",
+            site
+        ));
+    }
+    let output_type = field("Output type")?;
+    if !output_type.starts_with("GEOMETRIC cartesian") {
+        return Err(format!(
+            "unsupported output type '{}' (use VEC_CORR=NONE)",
+            output_type
+        ));
+    }
+    // Older responses show the reference plane separately.
+    let frame = fields
+        .get("Coordinate systm")
+        .copied()
+        .map_or_else(|| field("Reference frame"), Ok)?;
+    if !frame.starts_with("Ecliptic") {
+        return Err(format!(
+            "unsupported reference frame '{}' (use REF_PLANE=ECLIPTIC)",
+            frame
+        ));
+    }
+    let units = field("Output units")?;
+    let (pos_scale, vel_scale) = match units {
+        "KM-S" => (1.0, 1.0),
+        "KM-D" => (1.0, 1.0 / DAY),
+        "AU-D" => (AU, AU / DAY),
+        _ => return Err(format!("unsupported output units '{}'", units)),
+    };
+
+    let values = parse_entry(header, table)?;
+    let value = |label: &str| {
+        values
+            .get(label)
+            .copied()
+            .ok_or_else(|| format!("missing {} in vector table (use VEC_TABLE=2 or 3)", label))
+    };
+    let position = Point3::new(value("X")?, value("Y")?, value("Z")?) * pos_scale;
+    let velocity = Vector3::new(value("VX")?, value("VY")?, value("VZ")?) * vel_scale;
+
+    // Horizons epochs are in TDB unless another time scale was requested
+    // (TIME_TYPE).
+    let start_time = field("Start time")?;
+    let time_scale = match start_time.rsplit(' ').next().unwrap_or_default() {
+        "TDB" => TimeScale::Tdb,
+        "TT" => TimeScale::Tt,
+        "UT" => TimeScale::Utc,
+        _ => return Err(format!("unsupported time scale in '{}'", start_time)),
+    };
+    let jd = value("JD")?;
+    let millis = ((jd - 2440587.5) * DAY * 1e3).round() as i64;
+
+    Ok(Vectors {
+        target,
+        center: center.to_string(),
+        center_body: horizons_body(center),
+        timestamp: time::from_scale(&Utc.timestamp_millis(millis).naive_utc(), time_scale),
+        state: BodyState::new(position, velocity),
+    })
+}
+
+// Parses the first entry of a vector table into a map from labels (e.g. "X",
+// "VY") to values. The Julian day is stored under "JD".
+fn parse_entry(header: &str, table: &str) -> Result<HashMap<String, f64>, String> {
+    let mut lines = table.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next().ok_or("empty vector table")?;
+    let mut values = HashMap::new();
+
+    if first.contains(',') {
+        // CSV layout: the column names are on the last header line.
+        let columns = header
+            .lines()
+            .rev()
+            .find(|l| l.contains(','))
+            .ok_or("missing CSV column names")?;
+        for (name, value) in columns.split(',').zip(first.split(',')) {
+            let name = name.trim();
+            let name = if name.starts_with("JD") { "JD" } else { name };
+            if let Ok(v) = value.trim().parse() {
+                values.insert(name.to_string(), v);
+            }
+        }
+        return Ok(values);
+    }
+
+    // Text layout: "JD = A.D. <date>", followed by lines of values.
+    let (jd, _) = first.split_once('=').ok_or("invalid epoch line")?;
+    values.insert("JD".to_string(), parse_number(jd)?);
+    let rows: Vec<&str> = lines
+        .take_while(|l| !l.contains("A.D.") && !l.contains("B.C."))
+        .collect();
+
+    if rows.iter().any(|r| r.contains('=')) {
+        // Labeled values, e.g. " X = 1.29E+08 Y =-7.89E+07 Z = 2.68E+03".
+        for row in rows {
+            let row = row.replace('=', " = ");
+            let tokens: Vec<&str> = row.split_whitespace().collect();
+            for t in tokens.windows(3) {
+                if t[1] == "=" {
+                    values.insert(t[0].to_string(), parse_number(t[2])?);
+                }
+            }
+        }
+    } else {
+        // Unlabeled values; the labels are listed in the header, between the
+        // last two separator lines.
+        let mut label_lines: Vec<&str> = header
+            .lines()
+            .rev()
+            .skip_while(|l| !l.starts_with('*'))
+            .skip(1)
+            .take_while(|l| !l.starts_with('*'))
+            .collect();
+        label_lines.reverse();
+        let labels: Vec<&str> = label_lines
+            .iter()
+            .flat_map(|l| l.split_whitespace())
+            .filter(|l| !l.starts_with("JD"))
+            .collect();
+        let numbers = rows
+            .iter()
+            .flat_map(|r| r.split_whitespace())
+            .map(parse_number)
+            .collect::<Result<Vec<_>, _>>()?;
+        if labels.len() != numbers.len() {
+            return Err("vector table does not match the column names".to_string());
+        }
+        for (label, v) in labels.into_iter().zip(numbers) {
+            values.insert(label.to_string(), v);
+        }
+    }
+    Ok(values)
+}
+
+fn parse_number(s: &str) -> Result<f64, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("invalid number '{}'", s.trim()))
+}
+
+// Returns the body for a Horizons name like "Earth (399)".
+fn horizons_body(name: &str) -> Option<Body> {
+    let id = name.rsplit_once('(')?.1.trim_end_matches(')');
+    match id {
+        "10" => Some(Body::Sun),
+        "399" => Some(Body::Earth),
+        "301" => Some(Body::Moon),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EARTH_TEXT: &str = include_str!("testdata/horizons/earth-sun-text.txt");
+    const EARTH_AU: &str = include_str!("testdata/horizons/earth-sun-au-nolabels.txt");
+    const MOON_CSV: &str = include_str!("testdata/horizons/moon-sun-csv.txt");
+
+    fn assert_close(a: &BodyState, b: &BodyState) {
+        let dp = (a.position - b.position).norm() / b.position.coords.norm();
+        let dv = (a.velocity - b.velocity).norm() / b.velocity.norm();
+        assert!(dp < 1e-14 && dv < 1e-14, "{} {}", dp, dv);
+    }
+
+    #[test]
+    #[allow(clippy::excessive_precision)]
+    fn layouts() {
+        let expected = BodyState::new(
+            Point3::new(
+                1.290745457486534E+08,
+                -7.899200932997707E+07,
+                2.689484561856836E+03,
+            ),
+            Vector3::new(
+                1.507209745469294E+01,
+                2.530788781266470E+01,
+                -2.302676624889699E-03,
+            ),
+        );
+        let epoch = time::parse_time("2017-08-21 15:46:48", TimeScale::Tdb).unwrap();
+        let json = serde_json::json!({ "result": EARTH_TEXT }).to_string();
+        for text in [EARTH_TEXT, EARTH_AU, &json] {
+            let v = parse(text).unwrap();
+            assert_eq!(v.len(), 1);
+            assert_eq!(v[0].target, Body::Earth);
+            assert_eq!(v[0].center, "Sun (10)");
+            assert_eq!(v[0].center_body, Some(Body::Sun));
+            assert_eq!(v[0].timestamp, epoch);
+            assert_close(&v[0].state, &expected);
+        }
+
+        // Epochs in UT are taken as UTC.
+        let v = parse(&EARTH_TEXT.replace(" TDB", " UT")).unwrap();
+        assert_eq!(v[0].timestamp, Utc.ymd(2017, 8, 21).and_hms(15, 46, 48));
+
+        let v = parse(MOON_CSV).unwrap();
+        assert_eq!(v[0].target, Body::Moon);
+        assert_eq!(v[0].timestamp, epoch);
+        assert_close(
+            &v[0].state,
+            &BodyState::new(
+                Point3::new(
+                    1.287626991572680E+08,
+                    -7.878974778529878E+07,
+                    4.510853649180382E+03,
+                ),
+                Vector3::new(
+                    1.446304692640349E+01,
+                    2.444380218816157E+01,
+                    9.547778835418086E-02,
+                ),
+            ),
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        // Multiple responses can be concatenated.
+        let v = parse(&format!("{}\n{}", EARTH_TEXT, MOON_CSV)).unwrap();
+        let s = to_snapshot(&v).unwrap();
+        assert_eq!(s.position(Body::Sun), Point3::origin());
+        assert_close(s.state(Body::Earth), &v[0].state);
+        assert_close(s.state(Body::Moon), &v[1].state);
+
+        let err = to_snapshot(&parse(EARTH_TEXT).unwrap()).unwrap_err();
+        assert!(err.contains("missing vectors for Moon"), "{}", err);
+
+        let moon_2022 = MOON_CSV.replace("2457987.157500000", "2459715.563969907");
+        let v = parse(&format!("{}\n{}", EARTH_TEXT, moon_2022)).unwrap();
+        let err = to_snapshot(&v).unwrap_err();
+        assert!(err.contains("Moon vectors are for 2022-05-16"), "{}", err);
+    }
+
+    #[test]
+    fn errors() {
+        for (from, to, expected) in [
+            (
+                "Ecliptic of J2000.0",
+                "ICRF",
+                "table 1: unsupported reference frame 'ICRF'",
+            ),
+            ("KM-S", "AU-S", "unsupported output units 'AU-S'"),
+            (
+                "Earth (399)",
+                "Mars (499)",
+                "unsupported target body 'Mars (499)'",
+            ),
+            (
+                "BODY CENTER",
+                "Greenwich",
+                "unsupported center site 'Greenwich'",
+            ),
+            (
+                "GEOMETRIC cartesian states",
+                "Astrometric cartesian states",
+                "unsupported output type",
+            ),
+            ("Output units", "Units", "missing 'Output units'"),
+            (" VX=", " WX=", "missing VX in vector table"),
+            ("$$EOE", "", "missing $$EOE"),
+            (" TDB", " UT1", "unsupported time scale"),
+        ] {
+            let err = parse(&EARTH_TEXT.replace(from, to)).unwrap_err();
+            assert!(err.contains(expected), "{}", err);
+        }
+        let err = parse("{\"error\": \"No matches found.\"}").unwrap_err();
+        assert_eq!(err, "Horizons error: No matches found.");
+        assert!(parse("hello").is_err());
+    }
+}
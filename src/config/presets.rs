@@ -0,0 +1,387 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use kiss3d::nalgebra::{Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::Body,
+    geo::{Horizontal, LatLon},
+    simulation::{BodyState, IntegratorKind, Seconds, Snapshot},
+    time::{parse_time, TimeScale},
+};
+
+use super::{horizons, CameraSpec, LookAt, Observer, Preset};
+
+// On-disk representation of a preset. See presets/README.md.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct PresetFile {
+    name: String,
+    timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_scale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    integrator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    camera: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    observer: Option<ObserverFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span_days: Option<f64>,
+    #[serde(default)]
+    bodies: BTreeMap<String, BodyStateFile>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ObserverFile {
+    // Latitude and longitude, in degrees.
+    location: [f64; 2],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    look_at: Option<LookAtFile>,
+}
+
+// A body name, or [altitude, azimuth] in degrees.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum LookAtFile {
+    Body(String),
+    Sky([f64; 2]),
+}
+
+impl From<&Observer> for ObserverFile {
+    fn from(o: &Observer) -> Self {
+        Self {
+            location: [o.location.lat, o.location.lon],
+            look_at: Some(match o.look_at {
+                LookAt::Body(body) => LookAtFile::Body(body.props().name.to_string()),
+                LookAt::Sky(h) => LookAtFile::Sky([h.altitude, h.azimuth]),
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct BodyStateFile {
+    position: [f64; 3],
+    velocity: [f64; 3],
+}
+
+impl From<&BodyState> for BodyStateFile {
+    fn from(s: &BodyState) -> Self {
+        Self {
+            position: s.position.coords.into(),
+            velocity: s.velocity.into(),
+        }
+    }
+}
+
+impl From<&BodyStateFile> for BodyState {
+    fn from(s: &BodyStateFile) -> Self {
+        BodyState::new(s.position.into(), s.velocity.into())
+    }
+}
+
+// Loads all the presets in a directory, ordered by file name. Only .toml,
+// .json and .txt (Horizons) files are considered; subdirectories are ignored.
+pub fn load_dir(dir: &Path) -> Result<Vec<Preset>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("{}: {}", dir.display(), e))?
+            .path();
+        if path.is_file() && Format::from_path(&path).is_some() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths.iter().map(|p| load_file(p)).collect()
+}
+
+pub fn load_file(path: &Path) -> Result<Preset, String> {
+    let format = Format::from_path(path)
+        .ok_or_else(|| format!("{}: unknown preset format", path.display()))?;
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse(&contents, format).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Formats a snapshot as a TOML preset. Bodies at rest at the origin (e.g. the
+// center of Horizons vectors) are omitted.
+pub fn to_toml(name: &str, snapshot: &Snapshot) -> String {
+    let mut bodies = BTreeMap::new();
+    for body in Body::ALL {
+        let s = snapshot.state(body);
+        if s.position != Point3::origin() || s.velocity != Vector3::zeros() {
+            bodies.insert(body.props().name.to_string(), BodyStateFile::from(s));
+        }
+    }
+    let file = PresetFile {
+        name: name.to_string(),
+        timestamp: snapshot
+            .timestamp
+            .format("%Y-%m-%d %H:%M:%S%.f")
+            .to_string(),
+        time_scale: None,
+        integrator: None,
+        camera: None,
+        observer: None,
+        span_days: None,
+        bodies,
+    };
+    toml::to_string(&file).unwrap()
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Toml,
+    Json,
+    // JPL Horizons vector tables; see horizons.rs.
+    Horizons,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            "txt" => Some(Format::Horizons),
+            _ => None,
+        }
+    }
+}
+
+fn parse(contents: &str, format: Format) -> Result<Preset, String> {
+    let file: PresetFile = match format {
+        Format::Toml => toml::from_str(contents).map_err(|e| e.to_string())?,
+        Format::Json => serde_json::from_str(contents).map_err(|e| e.to_string())?,
+        Format::Horizons => return parse_horizons(contents),
+    };
+
+    let time_scale = match &file.time_scale {
+        Some(name) => {
+            TimeScale::from_name(name).ok_or_else(|| format!("unknown time scale '{}'", name))?
+        }
+        None => TimeScale::Utc,
+    };
+    let timestamp = parse_time(&file.timestamp, time_scale)?;
+    let states = parse_bodies(&file.bodies)?;
+    let integrator = match &file.integrator {
+        Some(name) => Some(
+            IntegratorKind::from_name(name)
+                .ok_or_else(|| format!("unknown integrator '{}'", name))?,
+        ),
+        None => None,
+    };
+    let camera = match &file.camera {
+        Some(name) => Some(
+            CameraSpec::by_description(name).ok_or_else(|| format!("unknown camera '{}'", name))?,
+        ),
+        None => None,
+    };
+    let observer = match &file.observer {
+        Some(o) => Some(parse_observer(o)?),
+        None => None,
+    };
+    let span = match file.span_days {
+        Some(days) if days > 0.0 && days <= Preset::MAX_SPAN_DAYS => {
+            Seconds(days * 86400.0).to_duration()
+        }
+        Some(days) => return Err(format!("invalid span_days {}", days)),
+        None => chrono::Duration::days(Preset::DEFAULT_SPAN_DAYS),
+    };
+
+    Ok(Preset {
+        name: file.name,
+        // The state vectors can be in any inertial frame (e.g. relative to the
+        // Sun); we convert them to the barycentric frame.
+        snapshot: Snapshot::new(timestamp, states).to_barycentric(),
+        integrator,
+        camera,
+        observer,
+        span,
+    })
+}
+
+pub(super) fn parse_bodies(
+    bodies: &BTreeMap<String, BodyStateFile>,
+) -> Result<Vec<(Body, BodyState)>, String> {
+    bodies
+        .iter()
+        .map(|(name, s)| {
+            let body = Body::from_name(name).ok_or_else(|| format!("unknown body '{}'", name))?;
+            Ok((body, BodyState::from(s)))
+        })
+        .collect()
+}
+
+pub(super) fn parse_observer(o: &ObserverFile) -> Result<Observer, String> {
+    let [lat, lon] = o.location;
+    if lat.abs() > 90.0 || lon.abs() > 180.0 {
+        return Err(format!("invalid observer location [{}, {}]", lat, lon));
+    }
+    let look_at = match &o.look_at {
+        None => LookAt::Body(Body::Sun),
+        Some(LookAtFile::Body(name)) => {
+            LookAt::Body(Body::from_name(name).ok_or_else(|| format!("unknown body '{}'", name))?)
+        }
+        Some(LookAtFile::Sky([altitude, azimuth])) => LookAt::Sky(Horizontal {
+            altitude: *altitude,
+            azimuth: *azimuth,
+        }),
+    };
+    Ok(Observer {
+        location: LatLon::new(lat, lon),
+        look_at,
+    })
+}
+
+// Loads a preset from saved Horizons responses (one for each body, possibly
+// concatenated).
+fn parse_horizons(contents: &str) -> Result<Preset, String> {
+    let snapshot = horizons::to_snapshot(&horizons::parse(contents)?)?;
+    Ok(Preset {
+        name: format!("Horizons {}", snapshot.timestamp.format("%Y-%m-%d %H:%M")),
+        snapshot: snapshot.to_barycentric(),
+        integrator: None,
+        camera: None,
+        observer: None,
+        span: chrono::Duration::days(Preset::DEFAULT_SPAN_DAYS),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::parse_utc;
+
+    #[test]
+    fn load_presets_dir() {
+        let presets = load_dir(Path::new("presets")).unwrap();
+        let names: Vec<&str> = presets.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Solar eclipse Aug 2017", "Lunar eclipse May 2022"]);
+
+        // The test presets in the subdirectory load as well.
+        for file in [
+            "presets/test/no-moon-inclination.toml",
+            "presets/test/high-moon-inclination.json",
+        ] {
+            load_file(Path::new(file)).unwrap();
+        }
+    }
+
+    #[test]
+    fn toml_and_json() {
+        let toml = r#"
+            name = "test"
+            timestamp = "2000-01-01 12:00"
+            integrator = "rk4"
+            camera = "Moon phase"
+            span_days = 30
+
+            [observer]
+            location = [36.97, -87.67]
+            look_at = [30.0, 180.0]
+
+            [bodies.Earth]
+            position = [152.10e6, 0.0, 0.0]
+            velocity = [0.0, 29.3, 0.0]
+        "#;
+        let json = r#"{
+            "name": "test",
+            "timestamp": "2000-01-01T12:00:00Z",
+            "integrator": "rk4",
+            "camera": "Moon phase",
+            "span_days": 30,
+            "observer": { "location": [36.97, -87.67], "look_at": [30.0, 180.0] },
+            "bodies": {
+                "Earth": { "position": [152.10e6, 0.0, 0.0], "velocity": [0.0, 29.3, 0.0] }
+            }
+        }"#;
+        for p in [
+            parse(toml, Format::Toml).unwrap(),
+            parse(json, Format::Json).unwrap(),
+        ] {
+            assert_eq!(p.name, "test");
+            assert_eq!(p.snapshot.timestamp, parse_utc("2000-01-01 12:00").unwrap());
+            assert_eq!(p.integrator, Some(IntegratorKind::Rk4));
+            assert_eq!(p.camera.unwrap().description, "Moon phase");
+            assert_eq!(p.span, chrono::Duration::days(30));
+            let observer = p.observer.unwrap();
+            assert_eq!(observer.location, LatLon::new(36.97, -87.67));
+            assert_eq!(
+                observer.look_at,
+                LookAt::Sky(Horizontal {
+                    altitude: 30.0,
+                    azimuth: 180.0
+                })
+            );
+            let d = p.snapshot.position(Body::Earth) - p.snapshot.position(Body::Sun);
+            assert_eq!(d, Vector3::new(152.10e6, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn horizons() {
+        let text = format!(
+            "{}\n{}",
+            include_str!("testdata/horizons/earth-sun-text.txt"),
+            include_str!("testdata/horizons/moon-sun-csv.txt")
+        );
+        let p = parse(&text, Format::Horizons).unwrap();
+        // The name has the UTC time (the vectors are for 15:46:48 TDB).
+        assert_eq!(p.name, "Horizons 2017-08-21 15:45");
+
+        // The vectors are the same as in the preset file.
+        let expected = load_file(Path::new("presets/2017-08-21-solar-eclipse.toml")).unwrap();
+        let toml = to_toml(
+            "test",
+            &horizons::to_snapshot(&horizons::parse(&text).unwrap()).unwrap(),
+        );
+        assert!(!toml.contains("Sun"), "{}", toml);
+        for p in [p, parse(&toml, Format::Toml).unwrap()] {
+            assert_eq!(p.snapshot.timestamp, expected.snapshot.timestamp);
+            for body in Body::ALL {
+                assert_eq!(p.snapshot.position(body), expected.snapshot.position(body));
+                assert_eq!(p.snapshot.velocity(body), expected.snapshot.velocity(body));
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let base = "name = \"test\"\ntimestamp = \"2000-01-01 00:00\"\n";
+        for (extra, expected) in [
+            ("camera = \"Mars\"", "unknown camera 'Mars'"),
+            ("integrator = \"Euler\"", "unknown integrator 'Euler'"),
+            (
+                "[bodies.Mars]\nposition = [0.0, 0.0, 0.0]\nvelocity = [0.0, 0.0, 0.0]",
+                "unknown body 'Mars'",
+            ),
+            ("speed = 1", "unknown field `speed`"),
+            ("time_scale = \"GPS\"", "unknown time scale 'GPS'"),
+            (
+                "[observer]\nlocation = [95.0, 0.0]",
+                "invalid observer location",
+            ),
+            (
+                "[observer]\nlocation = [0.0, 0.0]\nlook_at = \"Mars\"",
+                "unknown body 'Mars'",
+            ),
+            ("span_days = 0", "invalid span_days 0"),
+            ("span_days = 1e9", "invalid span_days"),
+            ("span_days = inf", "invalid span_days inf"),
+            ("span_days = nan", "invalid span_days"),
+        ] {
+            let err = parse(&format!("{}{}", base, extra), Format::Toml)
+                .err()
+                .unwrap();
+            assert!(err.contains(expected), "{}", err);
+        }
+        let err = parse("name = \"x\"\ntimestamp = \"soon\"", Format::Toml)
+            .err()
+            .unwrap();
+        assert!(err.contains("invalid time"), "{}", err);
+    }
+}
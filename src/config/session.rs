@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use chrono::SecondsFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::Body,
+    simulation::{IntegratorKind, Seconds, Snapshot},
+    state::{RenderState, SimulationState},
+    time::parse_utc,
+};
+
+use super::{
+    presets::{parse_bodies, parse_observer, BodyStateFile, ObserverFile},
+    CameraSpec, CameraView, Observer, RenderToggles,
+};
+
+// The full state of the simulation and of the view, which can be saved to a
+// file and restored later.
+#[derive(Clone)]
+pub struct Session {
+    // Name of the preset the simulation was started from. It is selected again
+    // when the session is restored, if it still exists.
+    pub preset: String,
+    pub snapshot: Snapshot,
+    // Simulated duration per elapsed second.
+    pub speed: chrono::Duration,
+    pub reverse: bool,
+    pub integrator: IntegratorKind,
+    pub camera: CameraSpec,
+    pub camera_view: CameraView,
+    pub observer: Observer,
+    pub toggles: RenderToggles,
+}
+
+// On-disk representation of a session (TOML).
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct SessionFile {
+    preset: String,
+    // UTC, in RFC 3339 format with nanoseconds.
+    timestamp: String,
+    // Simulated seconds per elapsed second.
+    speed: f64,
+    reverse: bool,
+    integrator: String,
+    camera: String,
+    camera_view: CameraViewFile,
+    observer: ObserverFile,
+    show: RenderToggles,
+    // State vectors of all the bodies, in the barycentric frame.
+    bodies: BTreeMap<String, BodyStateFile>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct CameraViewFile {
+    // km
+    distance: f64,
+    // Degrees.
+    pitch: f64,
+    yaw: f64,
+    fov: f64,
+}
+
+impl Session {
+    // Captures the current state of the simulation and of the renderer.
+    pub fn capture(sim_state: &dyn SimulationState, render_state: &dyn RenderState) -> Self {
+        Self {
+            preset: sim_state.preset().name.clone(),
+            snapshot: sim_state.snapshot(),
+            speed: sim_state.speed(),
+            reverse: sim_state.is_reverse(),
+            integrator: sim_state.integrator().get(),
+            camera: render_state.camera_focus().get(),
+            camera_view: render_state.camera_view(),
+            observer: render_state.observer(),
+            toggles: RenderToggles {
+                trails: render_state.show_trails(),
+                ecliptic: render_state.show_ecliptic(),
+                skybox: render_state.show_skybox(),
+                earth_axis: render_state.show_earth_axis(),
+                eclipse_path: render_state.show_eclipse_path(),
+                orbit_markers: render_state.show_orbit_markers(),
+            },
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_toml()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn to_toml(&self) -> String {
+        let bodies = Body::ALL
+            .iter()
+            .map(|b| {
+                let state = BodyStateFile::from(self.snapshot.state(*b));
+                (b.props().name.to_string(), state)
+            })
+            .collect();
+        let file = SessionFile {
+            preset: self.preset.clone(),
+            timestamp: self
+                .snapshot
+                .timestamp
+                .to_rfc3339_opts(SecondsFormat::Nanos, true),
+            speed: Seconds::from(self.speed).0,
+            reverse: self.reverse,
+            integrator: self.integrator.name().to_string(),
+            camera: self.camera.description.to_string(),
+            camera_view: CameraViewFile {
+                distance: self.camera_view.distance,
+                pitch: self.camera_view.pitch.to_degrees(),
+                yaw: self.camera_view.yaw.to_degrees(),
+                fov: self.camera_view.fov.to_degrees(),
+            },
+            observer: ObserverFile::from(&self.observer),
+            show: self.toggles,
+            bodies,
+        };
+        toml::to_string(&file).unwrap()
+    }
+
+    fn parse(contents: &str) -> Result<Self, String> {
+        let file: SessionFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+        if !file.speed.is_finite() || file.speed <= 0.0 {
+            return Err(format!("invalid speed {}", file.speed));
+        }
+        let view = &file.camera_view;
+        Ok(Self {
+            preset: file.preset,
+            snapshot: Snapshot::new(parse_utc(&file.timestamp)?, parse_bodies(&file.bodies)?),
+            speed: Seconds(file.speed).to_duration(),
+            reverse: file.reverse,
+            integrator: IntegratorKind::from_name(&file.integrator)
+                .ok_or_else(|| format!("unknown integrator '{}'", file.integrator))?,
+            camera: CameraSpec::by_description(&file.camera)
+                .ok_or_else(|| format!("unknown camera '{}'", file.camera))?,
+            camera_view: CameraView {
+                distance: view.distance,
+                pitch: view.pitch.to_radians(),
+                yaw: view.yaw.to_radians(),
+                fov: view.fov.to_radians(),
+            },
+            observer: parse_observer(&file.observer)?,
+            toggles: file.show,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{load_preset, LookAt},
+        geo::LatLon,
+        simulation,
+    };
+
+    fn session() -> Session {
+        let start = load_preset("2017-08-21-solar-eclipse.toml");
+        let t = start.timestamp + chrono::Duration::milliseconds(8_765_432);
+        Session {
+            preset: "Solar eclipse Aug 2017".to_string(),
+            snapshot: simulation::propagate(&start, IntegratorKind::Rk4, t, Seconds(60.0)),
+            speed: chrono::Duration::minutes(15),
+            reverse: true,
+            integrator: IntegratorKind::Yoshida4,
+            camera: CameraSpec::ALL[2],
+            camera_view: CameraView {
+                distance: 12345.678,
+                pitch: 0.1,
+                yaw: -2.5,
+                fov: 0.3,
+            },
+            observer: Observer {
+                location: LatLon::new(40.71, -74.01),
+                look_at: LookAt::Body(Body::Moon),
+            },
+            toggles: RenderToggles {
+                trails: true,
+                ecliptic: false,
+                skybox: true,
+                earth_axis: false,
+                eclipse_path: true,
+                orbit_markers: true,
+            },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let s = session();
+        let back = Session::parse(&s.to_toml()).unwrap();
+        assert_eq!(back.preset, s.preset);
+        // The state is restored exactly.
+        assert_eq!(back.snapshot.timestamp, s.snapshot.timestamp);
+        for body in Body::ALL {
+            assert_eq!(back.snapshot.position(body), s.snapshot.position(body));
+            assert_eq!(back.snapshot.velocity(body), s.snapshot.velocity(body));
+        }
+        assert_eq!(back.speed, s.speed);
+        assert!(back.reverse);
+        assert_eq!(back.integrator, s.integrator);
+        assert!(back.camera == s.camera);
+        let (a, b) = (back.camera_view, s.camera_view);
+        assert_eq!(a.distance, b.distance);
+        for (x, y) in [(a.pitch, b.pitch), (a.yaw, b.yaw), (a.fov, b.fov)] {
+            assert!((x - y).abs() < 1e-12);
+        }
+        assert_eq!(back.observer, s.observer);
+        assert_eq!(back.toggles, s.toggles);
+    }
+
+    #[test]
+    fn errors() {
+        let toml = session().to_toml();
+        for (from, to, expected) in [
+            ("\"Yoshida\"", "\"Euler\"", "unknown integrator 'Euler'"),
+            ("\"Moon phase\"", "\"Mars\"", "unknown camera 'Mars'"),
+            ("speed = 900.0", "speed = 0.0", "invalid speed 0"),
+            ("[bodies.Moon]", "[bodies.Mars]", "unknown body 'Mars'"),
+            ("eclipse_path = true", "", "missing field `eclipse_path`"),
+            (
+                "orbit_markers = true",
+                "orbit = true",
+                "unknown field `orbit`",
+            ),
+        ] {
+            assert!(toml.contains(from), "{}", toml);
+            let err = Session::parse(&toml.replace(from, to)).err().unwrap();
+            assert!(err.contains(expected), "{}", err);
+        }
+    }
+}
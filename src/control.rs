@@ -1,9 +1,11 @@
-use std::fmt::Write;
+use std::{fmt::Write, rc::Rc};
 
+use chrono::{DateTime, Utc};
 use kiss3d::event::{Action, Event, Key, WindowEvent};
 
 use crate::choice::Choice;
-use crate::config::{CameraSpec, Preset};
+use crate::config::{CameraSpec, Preset, Session};
+use crate::simulation::IntegratorKind;
 use crate::state::SimulationState;
 
 #[derive(Clone)]
@@ -15,32 +17,57 @@ pub enum ControlEvent {
     JumpBack,
     Faster,
     Slower,
-    SetSpeed(Choice<chrono::Duration>),
+    SetSpeed(chrono::Duration),
     Reverse,
+    ToggleFixedStep,
+    CycleIntegrator,
+    SetIntegrator(Choice<IntegratorKind>),
     LoadPreset(Choice<Preset>),
+    FindEclipses,
+    FindPhases,
+    FindOrbitEvents,
+    SeekTo(DateTime<Utc>),
     ToggleTrails,
     ToggleEcliptic,
     ToggleSkybox,
     ToggleEarthAxis,
+    ToggleEclipsePath,
+    ToggleOrbitMarkers,
     ToggleHelp,
+    Screenshot { show_ui: bool },
+    SaveSession,
+    LoadSession,
+    // Sent instead of LoadSession once the session file is loaded.
+    RestoreSession(Rc<Session>),
     Exit,
 }
 
 // Keyboard shortcut mappings. THe help message shows the mappings in this
 // order.
-const KEY_MAP: [(Key, ControlEvent); 14] = [
+const KEY_MAP: [(Key, ControlEvent); 25] = [
     (Key::Space, ControlEvent::StartStop),
     (Key::Tab, ControlEvent::CycleCamera),
     (Key::Equals, ControlEvent::Faster),
     (Key::Minus, ControlEvent::Slower),
     (Key::R, ControlEvent::Reverse),
+    (Key::F, ControlEvent::ToggleFixedStep),
     (Key::Left, ControlEvent::JumpBack),
     (Key::Right, ControlEvent::JumpForward),
+    (Key::I, ControlEvent::CycleIntegrator),
+    (Key::E, ControlEvent::FindEclipses),
+    (Key::M, ControlEvent::FindPhases),
+    (Key::N, ControlEvent::FindOrbitEvents),
     (Key::T, ControlEvent::ToggleTrails),
     (Key::G, ControlEvent::ToggleEcliptic),
     (Key::S, ControlEvent::ToggleSkybox),
     (Key::X, ControlEvent::ToggleEarthAxis),
+    (Key::P, ControlEvent::ToggleEclipsePath),
+    (Key::O, ControlEvent::ToggleOrbitMarkers),
     (Key::H, ControlEvent::ToggleHelp),
+    (Key::F12, ControlEvent::Screenshot { show_ui: true }),
+    (Key::F11, ControlEvent::Screenshot { show_ui: false }),
+    (Key::F5, ControlEvent::SaveSession),
+    (Key::F9, ControlEvent::LoadSession),
     (Key::Escape, ControlEvent::Exit),
     (Key::Q, ControlEvent::Exit),
 ];
@@ -76,19 +103,33 @@ impl ControlEvent {
         match self {
             ControlEvent::CycleCamera => "Cycle camera focus",
             ControlEvent::StartStop => "Start/stop simulation",
-            ControlEvent::Faster => "Increase the simulation speed",
-            ControlEvent::Slower => "Decrease the simulation speed",
+            ControlEvent::Faster => "Double the simulation speed",
+            ControlEvent::Slower => "Halve the simulation speed",
             ControlEvent::Reverse => "Reverse simulation",
+            ControlEvent::ToggleFixedStep => "Toggle fixed time step per frame",
             ControlEvent::SetCamera(_) => "Set camera focus",
             ControlEvent::JumpForward => "Jump forward",
             ControlEvent::JumpBack => "Jump backward",
             ControlEvent::SetSpeed(_) => "Set simulation speed",
+            ControlEvent::CycleIntegrator => "Cycle integration method",
+            ControlEvent::SetIntegrator(_) => "Set integration method",
             ControlEvent::LoadPreset(_) => "Load simulation preset",
+            ControlEvent::FindEclipses => "Find eclipses in the next year",
+            ControlEvent::FindPhases => "Find lunar phases in the next year",
+            ControlEvent::FindOrbitEvents => "Find lunar apsides and nodes in the next year",
+            ControlEvent::SeekTo(_) => "Jump to time",
             ControlEvent::ToggleTrails => "Toggle rendering of trails",
             ControlEvent::ToggleEcliptic => "Toggle rendering of orbital plane",
             ControlEvent::ToggleSkybox => "Toggle sky background",
             ControlEvent::ToggleEarthAxis => "Toggle earth axis",
+            ControlEvent::ToggleEclipsePath => "Toggle eclipse path on earth",
+            ControlEvent::ToggleOrbitMarkers => "Toggle lunar apsis and node markers",
             ControlEvent::ToggleHelp => "Toggle help",
+            ControlEvent::Screenshot { show_ui: true } => "Save screenshot",
+            ControlEvent::Screenshot { show_ui: false } => "Save screenshot without the UI",
+            ControlEvent::SaveSession => "Save session",
+            ControlEvent::LoadSession => "Load saved session",
+            ControlEvent::RestoreSession(_) => "Restore session",
             ControlEvent::Exit => "Exit",
         }
     }
@@ -0,0 +1,452 @@
+// Eclipse prediction: searches for solar and lunar eclipses by integrating
+// the simulation forward and measuring the shadow geometry.
+//
+// The geometry follows the usual (Besselian) approach: the shadow of the
+// occluder is a pair of cones (umbra and penumbra) whose axis goes through the
+// centers of the Sun and the occluder. The bodies are treated as spheres.
+
+use chrono::{DateTime, Utc};
+use kiss3d::nalgebra::{Point3, Vector3};
+
+use crate::{
+    body::Body,
+    simulation::{self, IntegratorKind, Seconds, Snapshot},
+};
+
+mod contacts;
+mod local;
+mod path;
+
+pub use contacts::{find_contacts, Contact, ContactTime};
+pub use local::{local_circumstances, LocalEclipse};
+pub use path::{find_path, PathPoint};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EclipseKind {
+    // The Moon's shadow falls on the Earth.
+    Solar,
+    // The Earth's shadow falls on the Moon.
+    Lunar,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EclipseType {
+    Total,
+    Annular,
+    // Total on part of the path and annular on the rest.
+    Hybrid,
+    Partial,
+    // Lunar eclipse where the Moon only enters the penumbra.
+    Penumbral,
+}
+
+impl EclipseType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Total => "Total",
+            Self::Annular => "Annular",
+            Self::Hybrid => "Hybrid",
+            Self::Partial => "Partial",
+            Self::Penumbral => "Penumbral",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Eclipse {
+    pub kind: EclipseKind,
+    pub eclipse_type: EclipseType,
+    // Time of greatest eclipse: when the shadow axis passes closest to the
+    // center of the Earth (solar) or the Moon passes closest to the shadow
+    // axis (lunar).
+    pub greatest: DateTime<Utc>,
+    // For solar eclipses, the fraction of the Sun's diameter covered by the
+    // Moon at greatest eclipse (for central eclipses, the ratio of the
+    // apparent diameters). For lunar eclipses, the fraction of the Moon's
+    // diameter inside the umbra (or the penumbra, for penumbral eclipses).
+    pub magnitude: f64,
+    // Distance between the shadow axis and the center of the Earth (solar) or
+    // the Moon (lunar) at greatest eclipse, in Earth radii. Positive when the
+    // axis passes north of the Earth's center (solar) or the Moon passes north
+    // of the axis (lunar).
+    pub gamma: f64,
+    // The state of the simulation at greatest eclipse.
+    pub snapshot: Snapshot,
+    // Contact times, in chronological order.
+    pub contacts: Vec<ContactTime>,
+    // Ground track of central solar eclipses (empty for other eclipses).
+    pub path: Vec<PathPoint>,
+}
+
+impl Eclipse {
+    pub fn description(&self) -> String {
+        let kind = match self.kind {
+            EclipseKind::Solar => "solar",
+            EclipseKind::Lunar => "lunar",
+        };
+        format!("{} {}", self.eclipse_type.name(), kind)
+    }
+}
+
+// Earth's atmosphere enlarges its shadow. Like current predictions, we use
+// Danjon's method: the Earth's radius is enlarged by 1/85.
+const EARTH_SHADOW_ENLARGEMENT: f64 = 1.0 + 1.0 / 85.0;
+
+// Speed of light, in km/s.
+const SPEED_OF_LIGHT: f64 = 299_792.458;
+
+// Returns the position of the body when the light seen at the given point (at
+// the snapshot time) passed it. The light takes about 1.3 s between the Earth
+// and the Moon, during which they move by about 40 km around the Sun; without
+// this, the eclipses would be about 40 s late.
+pub fn position_seen_from(snapshot: &Snapshot, body: Body, point: &Point3<f64>) -> Point3<f64> {
+    let position = snapshot.position(body);
+    let light_time = (point - position).norm() / SPEED_OF_LIGHT;
+    position - snapshot.velocity(body) * light_time
+}
+
+// Geometry of the shadow of an occluder, at the distance of a target body.
+pub struct Shadow {
+    // Position of the occluder when it cast the shadow that reaches the
+    // target.
+    pub occluder: Point3<f64>,
+    // Unit vector along the shadow axis (pointing away from the Sun).
+    pub axis: Vector3<f64>,
+    // Distance from the occluder to the target center, along the axis (km).
+    // Negative if the target is on the Sun side of the occluder.
+    pub distance: f64,
+    // Position of the target center relative to the closest point on the
+    // axis (km).
+    pub offset: Vector3<f64>,
+    // Radius of the penumbra at the target distance (km).
+    pub penumbra_radius: f64,
+    // Radius of the umbra at the target distance (km). Negative beyond the
+    // apex of the umbra cone, where the absolute value is the radius of the
+    // antumbra.
+    pub umbra_radius: f64,
+}
+
+impl Shadow {
+    pub fn new(snapshot: &Snapshot, occluder: Body, target: Body) -> Self {
+        let sun = snapshot.position(Body::Sun);
+        let target_pos = snapshot.position(target);
+        let occluder_pos = position_seen_from(snapshot, occluder, &target_pos);
+        let to_occluder = occluder_pos - sun;
+        let axis = to_occluder.normalize();
+        let r = target_pos - occluder_pos;
+        let distance = r.dot(&axis);
+        Self {
+            occluder: occluder_pos,
+            axis,
+            distance,
+            offset: r - axis * distance,
+            penumbra_radius: Self::penumbra_radius(snapshot, occluder, distance),
+            umbra_radius: Self::umbra_radius(snapshot, occluder, distance),
+        }
+    }
+
+    // Radius of the penumbra at the given distance behind the occluder.
+    pub fn penumbra_radius(snapshot: &Snapshot, occluder: Body, distance: f64) -> f64 {
+        let d = (snapshot.position(occluder) - snapshot.position(Body::Sun)).norm();
+        let r = Self::occluder_radius(occluder);
+        r + distance * (Body::Sun.radius64() + r) / d
+    }
+
+    // Radius of the umbra at the given distance behind the occluder (negative
+    // beyond the apex).
+    pub fn umbra_radius(snapshot: &Snapshot, occluder: Body, distance: f64) -> f64 {
+        let d = (snapshot.position(occluder) - snapshot.position(Body::Sun)).norm();
+        let r = Self::occluder_radius(occluder);
+        r - distance * (Body::Sun.radius64() - r) / d
+    }
+
+    fn occluder_radius(occluder: Body) -> f64 {
+        if occluder == Body::Earth {
+            occluder.radius64() * EARTH_SHADOW_ENLARGEMENT
+        } else {
+            occluder.radius64()
+        }
+    }
+}
+
+// Returns the direction of the Earth's north pole.
+pub fn earth_north(snapshot: &Snapshot) -> Vector3<f64> {
+    snapshot.earth_orientation() * Vector3::z()
+}
+
+// Classifies the eclipse at the given snapshot, which should be at the time of
+// greatest eclipse. Returns None if there is no eclipse. The contacts and the
+// path are not calculated.
+pub fn classify(kind: EclipseKind, snapshot: &Snapshot) -> Option<Eclipse> {
+    let (eclipse_type, magnitude, gamma) = match kind {
+        EclipseKind::Solar => classify_solar(snapshot)?,
+        EclipseKind::Lunar => classify_lunar(snapshot)?,
+    };
+    Some(Eclipse {
+        kind,
+        eclipse_type,
+        greatest: snapshot.timestamp,
+        magnitude,
+        gamma,
+        snapshot: *snapshot,
+        contacts: Vec::new(),
+        path: Vec::new(),
+    })
+}
+
+fn classify_solar(snapshot: &Snapshot) -> Option<(EclipseType, f64, f64)> {
+    let earth_radius = Body::Earth.radius64();
+    let shadow = Shadow::new(snapshot, Body::Moon, Body::Earth);
+    let dist = shadow.offset.norm();
+    if shadow.distance <= 0.0 || dist >= earth_radius + shadow.penumbra_radius {
+        return None;
+    }
+    // The offset is from the axis to the Earth's center; gamma is measured
+    // from the Earth's center to the axis.
+    let gamma = -shadow.offset.dot(&earth_north(snapshot)).signum() * dist / earth_radius;
+
+    if dist < earth_radius {
+        // Central eclipse. The axis meets the surface at this distance from
+        // the Moon:
+        let surface_distance = shadow.distance - (earth_radius.powi(2) - dist.powi(2)).sqrt();
+        let umbra_at_surface = Shadow::umbra_radius(snapshot, Body::Moon, surface_distance);
+        // Near the ends of the path, the surface is about as far as the
+        // Earth's center.
+        let eclipse_type = match (umbra_at_surface > 0.0, shadow.umbra_radius > 0.0) {
+            (true, true) => EclipseType::Total,
+            (true, false) => EclipseType::Hybrid,
+            _ => EclipseType::Annular,
+        };
+        // Ratio of the apparent diameters of the Moon and the Sun, seen from
+        // the point on the axis.
+        let moon = shadow.occluder;
+        let observer = moon + shadow.axis * surface_distance;
+        let moon_size = Body::Moon.radius64() / (moon - observer).norm();
+        let sun_size = Body::Sun.radius64() / (snapshot.position(Body::Sun) - observer).norm();
+        return Some((eclipse_type, moon_size / sun_size, gamma));
+    }
+
+    // Distance between the axis and the Earth's limb.
+    let limb_dist = dist - earth_radius;
+    let eclipse_type = if limb_dist < shadow.umbra_radius.abs() {
+        // The umbra (or antumbra) touches the Earth, but its axis misses.
+        if shadow.umbra_radius > 0.0 {
+            EclipseType::Total
+        } else {
+            EclipseType::Annular
+        }
+    } else {
+        EclipseType::Partial
+    };
+    let magnitude =
+        (shadow.penumbra_radius - limb_dist) / (shadow.penumbra_radius - shadow.umbra_radius);
+    Some((eclipse_type, magnitude, gamma))
+}
+
+fn classify_lunar(snapshot: &Snapshot) -> Option<(EclipseType, f64, f64)> {
+    let moon_radius = Body::Moon.radius64();
+    let shadow = Shadow::new(snapshot, Body::Earth, Body::Moon);
+    let dist = shadow.offset.norm();
+    if shadow.distance <= 0.0 {
+        return None;
+    }
+    let umbral = (shadow.umbra_radius + moon_radius - dist) / (2.0 * moon_radius);
+    let penumbral = (shadow.penumbra_radius + moon_radius - dist) / (2.0 * moon_radius);
+    let (eclipse_type, magnitude) = if umbral >= 1.0 {
+        (EclipseType::Total, umbral)
+    } else if umbral > 0.0 {
+        (EclipseType::Partial, umbral)
+    } else if penumbral > 0.0 {
+        (EclipseType::Penumbral, penumbral)
+    } else {
+        return None;
+    };
+    let gamma = shadow.offset.dot(&earth_north(snapshot)).signum() * dist / Body::Earth.radius64();
+    Some((eclipse_type, magnitude, gamma))
+}
+
+// Searches for all the eclipses between the snapshot's time and the end time
+// (which can be before the snapshot), integrating with steps of at most
+// max_step. The eclipses are returned in chronological order.
+pub fn find_eclipses(
+    snapshot: &Snapshot,
+    integrator: IntegratorKind,
+    end: DateTime<Utc>,
+    max_step: Seconds,
+) -> Vec<Eclipse> {
+    let mut result = Vec::new();
+    for kind in [EclipseKind::Solar, EclipseKind::Lunar] {
+        let search = Search {
+            kind,
+            integrator,
+            max_step,
+        };
+        search.run(snapshot, end, &mut result);
+    }
+    result.sort_by_key(|e| e.greatest);
+    result
+}
+
+// Returns the eclipse whose greatest eclipse is closest to the given time, if
+// it is within half a day (all the contacts are then within reach).
+pub fn nearest(eclipses: &[Eclipse], timestamp: DateTime<Utc>) -> Option<&Eclipse> {
+    eclipses
+        .iter()
+        .min_by_key(|e| (e.greatest - timestamp).num_milliseconds().abs())
+        .filter(|e| (e.greatest - timestamp).num_hours().abs() < 12)
+}
+
+// Interval between samples when scanning for eclipses. Eclipses last at least
+// a few hours (the partial phases), which is much longer than this.
+const SCAN_STEP: Seconds = Seconds(3600.0);
+
+// Greatest eclipse is located to within this precision.
+const PRECISION: Seconds = Seconds(0.1);
+
+struct Search {
+    kind: EclipseKind,
+    integrator: IntegratorKind,
+    max_step: Seconds,
+}
+
+impl Search {
+    fn shadow(&self, snapshot: &Snapshot) -> Shadow {
+        match self.kind {
+            EclipseKind::Solar => Shadow::new(snapshot, Body::Moon, Body::Earth),
+            EclipseKind::Lunar => Shadow::new(snapshot, Body::Earth, Body::Moon),
+        }
+    }
+
+    // Distance from the target to the shadow axis, or None if the target is on
+    // the wrong side of the occluder.
+    fn axis_distance(&self, snapshot: &Snapshot) -> Option<f64> {
+        let shadow = self.shadow(snapshot);
+        if shadow.distance > 0.0 {
+            Some(shadow.offset.norm())
+        } else {
+            None
+        }
+    }
+
+    fn propagate(&self, snapshot: &Snapshot, t: DateTime<Utc>) -> Snapshot {
+        simulation::propagate(snapshot, self.integrator, t, self.max_step)
+    }
+
+    // Scans the time range for local minima of the distance to the shadow
+    // axis; each one is a candidate for greatest eclipse.
+    fn run(&self, start: &Snapshot, end: DateTime<Utc>, result: &mut Vec<Eclipse>) {
+        let total = Seconds::from(end - start.timestamp);
+        let num_steps = (total.0.abs() / SCAN_STEP.0).ceil() as usize;
+        if num_steps == 0 {
+            return;
+        }
+        let step = total / num_steps as f64;
+
+        // The last three samples.
+        let mut samples: Vec<(Snapshot, Option<f64>)> = Vec::with_capacity(3);
+        let mut current = *start;
+        for i in 0..=num_steps {
+            if i > 0 {
+                let t = start.timestamp + (step * i as f64).to_duration();
+                current = self.propagate(&current, t);
+            }
+            if samples.len() == 3 {
+                samples.remove(0);
+            }
+            samples.push((current, self.axis_distance(&current)));
+
+            if let [(before, Some(d0)), (_, Some(d1)), (_, Some(d2))] = samples[..] {
+                if d1 <= d0 && d1 < d2 {
+                    let after = before.timestamp + (step * 2.0).to_duration();
+                    let greatest = self.refine_minimum(&before, after);
+                    if let Some(mut eclipse) = classify(self.kind, &greatest) {
+                        eclipse.contacts =
+                            find_contacts(self.kind, &greatest, self.integrator, self.max_step);
+                        eclipse.path = find_path(&eclipse, self.integrator, self.max_step);
+                        result.push(eclipse);
+                    }
+                }
+            }
+        }
+    }
+
+    // Finds the time of minimum axis distance between the snapshot time and
+    // the given time, using golden section search.
+    fn refine_minimum(&self, start: &Snapshot, end: DateTime<Utc>) -> Snapshot {
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let at = |x: f64| {
+            let s = self.propagate(start, start.timestamp + Seconds(x).to_duration());
+            (x, self.axis_distance(&s).unwrap_or(f64::MAX), s)
+        };
+        let (mut a, mut b) = (0.0, Seconds::from(end - start.timestamp).0);
+        let mut c = at(b - ratio * (b - a));
+        let mut d = at(a + ratio * (b - a));
+        while (b - a).abs() > PRECISION.0 {
+            if c.1 < d.1 {
+                b = d.0;
+                d = c;
+                c = at(b - ratio * (b - a));
+            } else {
+                a = c.0;
+                c = d;
+                d = at(a + ratio * (b - a));
+            }
+        }
+        if c.1 < d.1 {
+            c.2
+        } else {
+            d.2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::load_preset, time::parse_utc};
+
+    #[test]
+    fn find() {
+        let start = load_preset("2017-08-21-solar-eclipse.toml");
+        let end = parse_utc("2018-08-20 00:00").unwrap();
+        let eclipses = find_eclipses(&start, IntegratorKind::Yoshida4, end, Seconds(60.0));
+
+        // Published values (NASA/Espenak); times are UT.
+        let expected = [
+            ("2017-08-21 18:25:32", "Total solar", 1.0306, 0.4367),
+            ("2018-01-31 13:29:51", "Total lunar", 1.3155, -0.3014),
+            ("2018-02-15 20:51:20", "Partial solar", 0.5991, -1.2117),
+            ("2018-07-13 03:01:07", "Partial solar", 0.3365, -1.3542),
+            ("2018-07-27 20:21:44", "Total lunar", 1.6087, 0.1168),
+            ("2018-08-11 09:47:28", "Partial solar", 0.7368, 1.1476),
+        ];
+        let actual: Vec<String> = eclipses
+            .iter()
+            .map(|e| format!("{} {}", e.greatest, e.description()))
+            .collect();
+        assert_eq!(eclipses.len(), expected.len(), "{:#?}", actual);
+        // The first eclipse is within hours of the epoch: only the light time
+        // (about 40 s) and the time scales (69 s) matter.
+        let dt = Seconds::from(eclipses[0].greatest - parse_utc(expected[0].0).unwrap());
+        assert!(dt.0.abs() < 10.0, "{:?}", dt);
+        for (e, (time, description, magnitude, gamma)) in eclipses.iter().zip(expected) {
+            assert_eq!(e.description(), description);
+            // Later ones are off by up to 50 s, since the model leaves out the
+            // planets and the shape of the Earth.
+            let dt = Seconds::from(e.greatest - parse_utc(time).unwrap());
+            assert!(dt.0.abs() < 60.0, "{}: {:?}", description, dt);
+            assert!(
+                (e.magnitude - magnitude).abs() < 0.02,
+                "{}: magnitude {}",
+                time,
+                e.magnitude
+            );
+            assert!(
+                (e.gamma - gamma).abs() < 0.005,
+                "{}: gamma {}",
+                time,
+                e.gamma
+            );
+        }
+    }
+}
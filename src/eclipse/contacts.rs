@@ -0,0 +1,310 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    body::Body,
+    simulation::{self, IntegratorKind, Seconds, Snapshot},
+};
+
+use super::{EclipseKind, Shadow};
+
+// Contact instants of an eclipse. Each pair of contacts happens when the
+// distance between the shadow axis and the center of the Earth (solar) or the
+// Moon (lunar) crosses a threshold:
+//  - P1, P4: external contacts of the penumbra;
+//  - P2, P3: internal contacts of the penumbra;
+//  - U1, U4: external contacts of the umbra (or antumbra);
+//  - U2, U3: internal contacts of the umbra (or antumbra);
+//  - CentralBegin, CentralEnd: the shadow axis touches the Earth's limb
+//    (solar only); the central line begins and ends. These are not C1 to C4,
+//    which usually name the contacts of the disks seen from a place.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Contact {
+    P1,
+    P2,
+    P3,
+    P4,
+    U1,
+    U2,
+    U3,
+    U4,
+    CentralBegin,
+    CentralEnd,
+}
+
+impl Contact {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Contact::P1 => "P1",
+            Contact::P2 => "P2",
+            Contact::P3 => "P3",
+            Contact::P4 => "P4",
+            Contact::U1 => "U1",
+            Contact::U2 => "U2",
+            Contact::U3 => "U3",
+            Contact::U4 => "U4",
+            Contact::CentralBegin => "CB",
+            Contact::CentralEnd => "CE",
+        }
+    }
+
+    pub fn description(&self, kind: EclipseKind) -> &'static str {
+        match (kind, self) {
+            (EclipseKind::Solar, Contact::P1) => "Partial eclipse begins",
+            (EclipseKind::Solar, Contact::P2) => "Penumbra fully on Earth",
+            (EclipseKind::Solar, Contact::P3) => "Penumbra starts leaving Earth",
+            (EclipseKind::Solar, Contact::P4) => "Partial eclipse ends",
+            (EclipseKind::Solar, Contact::U1) => "Umbral eclipse begins",
+            (EclipseKind::Solar, Contact::U2) => "Umbra fully on Earth",
+            (EclipseKind::Solar, Contact::U3) => "Umbra starts leaving Earth",
+            (EclipseKind::Solar, Contact::U4) => "Umbral eclipse ends",
+            (EclipseKind::Solar, Contact::CentralBegin) => "Central eclipse begins",
+            (EclipseKind::Solar, Contact::CentralEnd) => "Central eclipse ends",
+            (EclipseKind::Lunar, Contact::P1) => "Penumbral eclipse begins",
+            (EclipseKind::Lunar, Contact::P2) => "Moon fully in penumbra",
+            (EclipseKind::Lunar, Contact::P3) => "Moon starts leaving penumbra",
+            (EclipseKind::Lunar, Contact::P4) => "Penumbral eclipse ends",
+            (EclipseKind::Lunar, Contact::U1) => "Partial eclipse begins",
+            (EclipseKind::Lunar, Contact::U2) => "Total eclipse begins",
+            (EclipseKind::Lunar, Contact::U3) => "Total eclipse ends",
+            (EclipseKind::Lunar, Contact::U4) => "Partial eclipse ends",
+            // find_contacts doesn't return them.
+            (EclipseKind::Lunar, Contact::CentralBegin | Contact::CentralEnd) => {
+                unreachable!("lunar eclipses have no central line")
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ContactTime {
+    pub contact: Contact,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone)]
+enum Threshold {
+    PenumbraExternal,
+    PenumbraInternal,
+    UmbraExternal,
+    UmbraInternal,
+    Axis,
+}
+
+impl Threshold {
+    // Returns the (first, last) contacts and the threshold for each pair.
+    const ALL: [(Contact, Contact, Threshold); 5] = [
+        (Contact::P1, Contact::P4, Threshold::PenumbraExternal),
+        (Contact::P2, Contact::P3, Threshold::PenumbraInternal),
+        (Contact::U1, Contact::U4, Threshold::UmbraExternal),
+        (Contact::U2, Contact::U3, Threshold::UmbraInternal),
+        (Contact::CentralBegin, Contact::CentralEnd, Threshold::Axis),
+    ];
+
+    // Axis distance at which the contact happens, for a target of the given
+    // radius.
+    fn distance(&self, target_radius: f64, shadow: &Shadow) -> f64 {
+        let umbra_radius = shadow.umbra_radius.abs();
+        match self {
+            Threshold::PenumbraExternal => target_radius + shadow.penumbra_radius,
+            Threshold::PenumbraInternal => (target_radius - shadow.penumbra_radius).abs(),
+            Threshold::UmbraExternal => target_radius + umbra_radius,
+            Threshold::UmbraInternal => (target_radius - umbra_radius).abs(),
+            Threshold::Axis => target_radius,
+        }
+    }
+}
+
+// Contacts are searched for up to this far from greatest eclipse.
+const MAX_DURATION: Seconds = Seconds(8.0 * 3600.0);
+
+// Step used to bracket the contacts before refining them.
+const BRACKET_STEP: Seconds = Seconds(600.0);
+
+// Contacts are located to within this precision.
+const PRECISION: Seconds = Seconds(0.1);
+
+// Computes the contacts of an eclipse, given the state at greatest eclipse.
+// Contacts that don't happen (e.g. U2 and U3 for a partial lunar eclipse) are
+// omitted. The result is in chronological order.
+pub fn find_contacts(
+    kind: EclipseKind,
+    greatest: &Snapshot,
+    integrator: IntegratorKind,
+    max_step: Seconds,
+) -> Vec<ContactTime> {
+    let finder = ContactFinder {
+        kind,
+        integrator,
+        max_step,
+    };
+    let mut result = Vec::new();
+    for (first, last, threshold) in Threshold::ALL {
+        if matches!(threshold, Threshold::Axis) && kind == EclipseKind::Lunar {
+            continue;
+        }
+        for (contact, direction) in [(first, -1.0), (last, 1.0)] {
+            if let Some(timestamp) = finder.find(greatest, threshold, direction) {
+                result.push(ContactTime { contact, timestamp });
+            }
+        }
+    }
+    result.sort_by_key(|c| c.timestamp);
+    result
+}
+
+struct ContactFinder {
+    kind: EclipseKind,
+    integrator: IntegratorKind,
+    max_step: Seconds,
+}
+
+impl ContactFinder {
+    // Returns the axis distance minus the contact threshold; negative when
+    // inside.
+    fn eval(&self, snapshot: &Snapshot, threshold: Threshold) -> f64 {
+        let (shadow, target) = match self.kind {
+            EclipseKind::Solar => (Shadow::new(snapshot, Body::Moon, Body::Earth), Body::Earth),
+            EclipseKind::Lunar => (Shadow::new(snapshot, Body::Earth, Body::Moon), Body::Moon),
+        };
+        shadow.offset.norm() - threshold.distance(target.radius64(), &shadow)
+    }
+
+    fn propagate(&self, snapshot: &Snapshot, offset: Seconds) -> Snapshot {
+        let t = snapshot.timestamp + offset.to_duration();
+        simulation::propagate(snapshot, self.integrator, t, self.max_step)
+    }
+
+    // Finds the contact before (direction -1) or after (direction 1) greatest
+    // eclipse.
+    fn find(
+        &self,
+        greatest: &Snapshot,
+        threshold: Threshold,
+        direction: f64,
+    ) -> Option<DateTime<Utc>> {
+        if self.eval(greatest, threshold) >= 0.0 {
+            return None;
+        }
+        // Step away from greatest eclipse until we are outside.
+        let mut inside = *greatest;
+        let mut step = BRACKET_STEP * direction;
+        loop {
+            let s = self.propagate(&inside, step);
+            if self.eval(&s, threshold) >= 0.0 {
+                break;
+            }
+            if Seconds::from(s.timestamp - greatest.timestamp).0.abs() > MAX_DURATION.0 {
+                return None;
+            }
+            inside = s;
+        }
+        // Bisect between inside and inside + step.
+        while step.0.abs() > PRECISION.0 {
+            step = step * 0.5;
+            let s = self.propagate(&inside, step);
+            if self.eval(&s, threshold) < 0.0 {
+                inside = s;
+            }
+        }
+        Some(inside.timestamp + (step * 0.5).to_duration())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::load_preset,
+        time::{parse_time, parse_utc, TimeScale},
+    };
+
+    // Checks the sequence of contacts, and the times of those with a published
+    // value (Espenak) in the given time scale. Returns the eclipse.
+    fn check(
+        preset: &str,
+        kind: EclipseKind,
+        expected: &[(Contact, Option<&str>)],
+        scale: TimeScale,
+        tolerance: f64,
+    ) -> super::super::Eclipse {
+        let start = load_preset(preset);
+        let end = start.timestamp + chrono::Duration::days(1);
+        let mut eclipses =
+            super::super::find_eclipses(&start, IntegratorKind::Rk4, end, Seconds(60.0));
+        assert_eq!(eclipses.len(), 1);
+        let e = eclipses.remove(0);
+        assert_eq!(e.kind, kind);
+
+        let actual: Vec<Contact> = e.contacts.iter().map(|c| c.contact).collect();
+        let contacts: Vec<Contact> = expected.iter().map(|(c, _)| *c).collect();
+        assert_eq!(actual, contacts);
+        for (c, (contact, time)) in e.contacts.iter().zip(expected) {
+            if let Some(time) = time {
+                let dt = Seconds::from(c.timestamp - parse_time(time, scale).unwrap());
+                assert!(dt.0.abs() < tolerance, "{}: {:?}", contact.name(), dt);
+            }
+        }
+        e
+    }
+
+    #[test]
+    fn solar() {
+        use Contact::*;
+        // The published times are in UT (greatest eclipse was at 18:26:40 TD,
+        // 18:25:32 UT).
+        let e = check(
+            "2017-08-21-solar-eclipse.toml",
+            EclipseKind::Solar,
+            &[
+                (P1, Some("2017-08-21 15:46:48")),
+                (U1, Some("2017-08-21 16:48:34")),
+                (CentralBegin, None),
+                (U2, None),
+                (P2, None),
+                (P3, None),
+                (U3, None),
+                (CentralEnd, None),
+                (U4, Some("2017-08-21 20:02:33")),
+                (P4, Some("2017-08-21 21:04:20")),
+            ],
+            TimeScale::Utc,
+            20.0,
+        );
+
+        // U2 and U3 happen when the small umbra (about 30 km) is just inside
+        // the Earth's limb, which it crosses at a grazing angle, so they are
+        // much more sensitive to errors in the geometry.
+        let at = |contact: Contact| {
+            e.contacts
+                .iter()
+                .find(|c| c.contact == contact)
+                .unwrap()
+                .timestamp
+        };
+        for (contact, time) in [(U2, "2017-08-21 16:50:15"), (U3, "2017-08-21 20:00:52")] {
+            let dt = Seconds::from(at(contact) - parse_utc(time).unwrap());
+            assert!(dt.0.abs() < 60.0, "{}: {:?}", contact.name(), dt);
+        }
+    }
+
+    #[test]
+    fn lunar() {
+        use Contact::*;
+        check(
+            "2022-05-16-lunar-eclipse.toml",
+            EclipseKind::Lunar,
+            &[
+                (P1, Some("2022-05-16 01:33:19")),
+                (U1, Some("2022-05-16 02:29:04")),
+                (P2, None),
+                (U2, Some("2022-05-16 03:30:15")),
+                (U3, Some("2022-05-16 04:55:08")),
+                (P3, None),
+                (U4, Some("2022-05-16 05:56:19")),
+                (P4, Some("2022-05-16 06:52:00")),
+            ],
+            TimeScale::Tt,
+            20.0,
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    body::Body,
+    geo::{self, Horizontal, LatLon},
+    simulation::{self, IntegratorKind, Seconds, Snapshot},
+};
+
+use super::{position_seen_from, Contact, Eclipse, EclipseKind, EclipseType};
+
+// Circumstances of a solar eclipse for an observer at a given location.
+#[derive(Copy, Clone, Debug)]
+pub struct LocalEclipse {
+    pub observer: LatLon,
+    // Total or annular if the observer is inside the path, otherwise partial.
+    pub eclipse_type: EclipseType,
+    // The Moon's disk first and last touches the Sun's disk.
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    // Closest approach of the two disks.
+    pub maximum: DateTime<Utc>,
+    // Start and end of totality or annularity.
+    pub central: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    // Fraction of the Sun's diameter covered by the Moon at maximum (for
+    // central eclipses, the ratio of the apparent diameters).
+    pub magnitude: f64,
+    // Fraction of the Sun's disk area covered by the Moon at maximum.
+    pub obscuration: f64,
+    // Position of the Sun in the sky at start, maximum and end. The eclipse
+    // is only visible while the Sun is above the horizon.
+    pub sun_at_start: Horizontal,
+    pub sun_at_maximum: Horizontal,
+    pub sun_at_end: Horizontal,
+}
+
+impl LocalEclipse {
+    // Returns true if the Sun is above the horizon for some part of the
+    // eclipse.
+    pub fn is_visible(&self) -> bool {
+        [self.sun_at_start, self.sun_at_maximum, self.sun_at_end]
+            .iter()
+            .any(|h| h.altitude > 0.0)
+    }
+}
+
+// Apparent disks of the Sun and the Moon, for an observer. All angles are in
+// radians.
+struct Disks {
+    // Angular distance between the centers.
+    separation: f64,
+    sun_radius: f64,
+    moon_radius: f64,
+}
+
+impl Disks {
+    fn new(snapshot: &Snapshot, observer: &LatLon) -> Self {
+        let pos = geo::observer_position(snapshot, observer);
+        let to_sun = snapshot.position(Body::Sun) - pos;
+        let to_moon = position_seen_from(snapshot, Body::Moon, &pos) - pos;
+        Self {
+            separation: to_sun.angle(&to_moon),
+            sun_radius: (Body::Sun.radius64() / to_sun.norm()).asin(),
+            moon_radius: (Body::Moon.radius64() / to_moon.norm()).asin(),
+        }
+    }
+
+    // Negative while the disks overlap.
+    fn partial(&self) -> f64 {
+        self.separation - (self.sun_radius + self.moon_radius)
+    }
+
+    // Negative while one disk is inside the other.
+    fn central(&self) -> f64 {
+        self.separation - (self.sun_radius - self.moon_radius).abs()
+    }
+
+    fn magnitude(&self) -> f64 {
+        if self.central() < 0.0 {
+            self.moon_radius / self.sun_radius
+        } else {
+            (self.sun_radius + self.moon_radius - self.separation) / (2.0 * self.sun_radius)
+        }
+    }
+
+    // Fraction of the Sun's disk area covered by the Moon's disk.
+    fn obscuration(&self) -> f64 {
+        let (d, r1, r2) = (self.separation, self.sun_radius, self.moon_radius);
+        if self.partial() >= 0.0 {
+            return 0.0;
+        }
+        if self.central() <= 0.0 {
+            return (r2 / r1).powi(2).min(1.0);
+        }
+        // Area of the intersection of two circles.
+        let a1 = r1 * r1 * ((d * d + r1 * r1 - r2 * r2) / (2.0 * d * r1)).acos();
+        let a2 = r2 * r2 * ((d * d + r2 * r2 - r1 * r1) / (2.0 * d * r2)).acos();
+        let a3 = 0.5 * ((-d + r1 + r2) * (d + r1 - r2) * (d - r1 + r2) * (d + r1 + r2)).sqrt();
+        (a1 + a2 - a3) / (std::f64::consts::PI * r1 * r1)
+    }
+}
+
+// Interval between samples when scanning the eclipse.
+const SCAN_STEP: Seconds = Seconds(60.0);
+
+// Times are located to within this precision.
+const PRECISION: Seconds = Seconds(0.1);
+
+// Computes the circumstances of a solar eclipse for an observer at the given
+// location. Returns None if the eclipse is not seen from there (ignoring the
+// horizon) or if it is not a solar eclipse.
+pub fn local_circumstances(
+    eclipse: &Eclipse,
+    observer: LatLon,
+    integrator: IntegratorKind,
+    max_step: Seconds,
+) -> Option<LocalEclipse> {
+    let contact = |c: Contact| {
+        eclipse
+            .contacts
+            .iter()
+            .find(|ct| ct.contact == c)
+            .map(|ct| ct.timestamp)
+    };
+    let (start, end) = match (eclipse.kind, contact(Contact::P1), contact(Contact::P4)) {
+        (EclipseKind::Solar, Some(start), Some(end)) => (start, end),
+        _ => return None,
+    };
+    let search = LocalSearch {
+        observer,
+        integrator,
+        max_step,
+    };
+
+    // Scan the whole eclipse (the observer can't see anything outside of
+    // [P1, P4]) to find the closest approach of the disks.
+    let total = Seconds::from(end - start);
+    let num_steps = ((total / SCAN_STEP).ceil() as usize).max(1);
+    let step = total / num_steps as f64;
+    let mut samples = Vec::with_capacity(num_steps + 1);
+    let mut snapshot = simulation::propagate(&eclipse.snapshot, integrator, start, max_step);
+    for i in 0..=num_steps {
+        if i > 0 {
+            let t = start + (step * i as f64).to_duration();
+            snapshot = simulation::propagate(&snapshot, integrator, t, max_step);
+        }
+        samples.push(snapshot);
+    }
+
+    let (max_idx, _) = samples
+        .iter()
+        .map(|s| search.disks(s).separation)
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    let maximum = search.refine_maximum(
+        &samples[max_idx.saturating_sub(1)],
+        samples[(max_idx + 1).min(num_steps)].timestamp,
+    );
+    let max_disks = search.disks(&maximum);
+    if max_disks.partial() >= 0.0 {
+        return None;
+    }
+
+    let start = search.exit(&maximum, Disks::partial, -1.0);
+    let end = search.exit(&maximum, Disks::partial, 1.0);
+    let central = if max_disks.central() < 0.0 {
+        Some((
+            search.exit(&maximum, Disks::central, -1.0),
+            search.exit(&maximum, Disks::central, 1.0),
+        ))
+    } else {
+        None
+    };
+    let eclipse_type = match central {
+        Some(_) if max_disks.moon_radius > max_disks.sun_radius => EclipseType::Total,
+        Some(_) => EclipseType::Annular,
+        None => EclipseType::Partial,
+    };
+    let sun_at = |t: DateTime<Utc>| {
+        let s = simulation::propagate(&maximum, integrator, t, max_step);
+        geo::horizontal(&s, &observer, Body::Sun)
+    };
+
+    Some(LocalEclipse {
+        observer,
+        eclipse_type,
+        start,
+        end,
+        maximum: maximum.timestamp,
+        central,
+        magnitude: max_disks.magnitude(),
+        obscuration: max_disks.obscuration(),
+        sun_at_start: sun_at(start),
+        sun_at_maximum: geo::horizontal(&maximum, &observer, Body::Sun),
+        sun_at_end: sun_at(end),
+    })
+}
+
+struct LocalSearch {
+    observer: LatLon,
+    integrator: IntegratorKind,
+    max_step: Seconds,
+}
+
+impl LocalSearch {
+    fn disks(&self, snapshot: &Snapshot) -> Disks {
+        Disks::new(snapshot, &self.observer)
+    }
+
+    fn propagate(&self, snapshot: &Snapshot, t: DateTime<Utc>) -> Snapshot {
+        simulation::propagate(snapshot, self.integrator, t, self.max_step)
+    }
+
+    // Finds the time when f stops being negative, going backward (direction
+    // -1) or forward (direction 1) from the given snapshot, where f must be
+    // negative.
+    fn exit(&self, inside: &Snapshot, f: fn(&Disks) -> f64, direction: f64) -> DateTime<Utc> {
+        let mut inside = *inside;
+        let mut step = SCAN_STEP * direction;
+        loop {
+            let s = self.propagate(&inside, inside.timestamp + step.to_duration());
+            if f(&self.disks(&s)) >= 0.0 {
+                break;
+            }
+            inside = s;
+        }
+        // Bisect between inside and inside + step.
+        while step.0.abs() > PRECISION.0 {
+            step = step * 0.5;
+            let s = self.propagate(&inside, inside.timestamp + step.to_duration());
+            if f(&self.disks(&s)) < 0.0 {
+                inside = s;
+            }
+        }
+        inside.timestamp + (step * 0.5).to_duration()
+    }
+
+    // Finds the time of minimum separation between the snapshot time and the
+    // given time, using golden section search.
+    fn refine_maximum(&self, start: &Snapshot, end: DateTime<Utc>) -> Snapshot {
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let at = |x: f64| {
+            let s = self.propagate(start, start.timestamp + Seconds(x).to_duration());
+            (x, self.disks(&s).separation, s)
+        };
+        let (mut a, mut b) = (0.0, Seconds::from(end - start.timestamp).0);
+        let mut c = at(b - ratio * (b - a));
+        let mut d = at(a + ratio * (b - a));
+        while (b - a).abs() > PRECISION.0 {
+            if c.1 < d.1 {
+                b = d.0;
+                d = c;
+                c = at(b - ratio * (b - a));
+            } else {
+                a = c.0;
+                c = d;
+                d = at(a + ratio * (b - a));
+            }
+        }
+        if c.1 < d.1 {
+            c.2
+        } else {
+            d.2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::load_preset, eclipse::find_eclipses, time::parse_utc};
+
+    fn eclipse_2017() -> Eclipse {
+        let start = load_preset("2017-08-21-solar-eclipse.toml");
+        let end = start.timestamp + chrono::Duration::days(1);
+        let mut eclipses = find_eclipses(&start, IntegratorKind::Rk4, end, Seconds(60.0));
+        assert_eq!(eclipses.len(), 1);
+        eclipses.remove(0)
+    }
+
+    #[test]
+    fn new_york() {
+        let e = eclipse_2017();
+        let local = local_circumstances(
+            &e,
+            LatLon::new(40.71, -74.01),
+            IntegratorKind::Rk4,
+            Seconds(60.0),
+        )
+        .unwrap();
+
+        // Published values (timeanddate.com).
+        assert_eq!(local.eclipse_type, EclipseType::Partial);
+        assert!(local.central.is_none());
+        for (actual, expected) in [
+            (local.start, "2017-08-21 17:23:03"),
+            (local.maximum, "2017-08-21 18:44:10"),
+            (local.end, "2017-08-21 20:00:27"),
+        ] {
+            let dt = Seconds::from(actual - parse_utc(expected).unwrap());
+            assert!(dt.0.abs() < 120.0, "{}: {:?}", expected, dt);
+        }
+        assert!(
+            (local.obscuration - 0.716).abs() < 0.03,
+            "{}",
+            local.obscuration
+        );
+        // Position of the Sun at the published maximum, from its low accuracy
+        // coordinates (Meeus, chapter 25). It moves by about 0.5 degree per
+        // minute.
+        assert!((local.sun_at_maximum.altitude - 53.1).abs() < 1.0);
+        assert!((local.sun_at_maximum.azimuth - 226.1).abs() < 2.0);
+        assert!(local.is_visible());
+    }
+
+    #[test]
+    fn nashville() {
+        // Nashville was inside the path of totality.
+        let e = eclipse_2017();
+        let local = local_circumstances(
+            &e,
+            LatLon::new(36.16, -86.78),
+            IntegratorKind::Rk4,
+            Seconds(60.0),
+        )
+        .unwrap();
+        assert_eq!(local.eclipse_type, EclipseType::Total);
+        assert_eq!(local.obscuration, 1.0);
+        let (t1, t2) = local.central.unwrap();
+        assert!(local.start < t1 && t1 < local.maximum && local.maximum < t2);
+        assert!(t2 < local.end);
+        // Totality lasted 1m57s. Our path is shifted towards Nashville, where
+        // totality is longer (up to 2m40s).
+        let duration = Seconds::from(t2 - t1).0;
+        assert!(duration > 100.0 && duration < 200.0, "{}", duration);
+    }
+
+    #[test]
+    fn not_seen() {
+        // Sydney is far outside the penumbra.
+        let e = eclipse_2017();
+        let local = local_circumstances(
+            &e,
+            LatLon::new(-33.87, 151.21),
+            IntegratorKind::Rk4,
+            Seconds(60.0),
+        );
+        assert!(local.is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use chrono::{DateTime, Utc};
+use kiss3d::nalgebra::{Point3, Vector3};
+
+use crate::{
+    body::Body,
+    geo::{self, LatLon},
+    simulation::{self, IntegratorKind, Seconds, Snapshot},
+};
+
+use super::{Contact, Eclipse, EclipseKind, Shadow};
+
+// A point of the ground track of a central solar eclipse.
+#[derive(Copy, Clone, Debug)]
+pub struct PathPoint {
+    pub timestamp: DateTime<Utc>,
+    // Where the shadow axis meets the Earth's surface.
+    pub central: LatLon,
+    // Northern and southern limits of the umbra (or antumbra). None near the
+    // ends of the path, where the limit misses the Earth.
+    pub north: Option<LatLon>,
+    pub south: Option<LatLon>,
+    // True if the eclipse is total on the central line, false if annular.
+    pub total: bool,
+}
+
+impl PathPoint {
+    // Width of the path, in km.
+    pub fn width(&self) -> Option<f64> {
+        Some(self.north?.distance(&self.south?))
+    }
+}
+
+// Interval between the points of the path.
+const PATH_STEP: Seconds = Seconds(60.0);
+
+// Computes the ground track of a central solar eclipse, from the beginning to
+// the end of the central line, as seen on the rotating Earth. Returns an
+// empty path for other eclipses.
+pub fn find_path(
+    eclipse: &Eclipse,
+    integrator: IntegratorKind,
+    max_step: Seconds,
+) -> Vec<PathPoint> {
+    let contact = |c: Contact| {
+        eclipse
+            .contacts
+            .iter()
+            .find(|ct| ct.contact == c)
+            .map(|ct| ct.timestamp)
+    };
+    let (start, end) = match (
+        eclipse.kind,
+        contact(Contact::CentralBegin),
+        contact(Contact::CentralEnd),
+    ) {
+        (EclipseKind::Solar, Some(start), Some(end)) => (start, end),
+        _ => return Vec::new(),
+    };
+    let total = Seconds::from(end - start);
+    let num_steps = ((total / PATH_STEP).ceil() as usize).max(1);
+    let step = total / num_steps as f64;
+
+    let mut result = Vec::with_capacity(num_steps + 1);
+    let mut snapshot = simulation::propagate(&eclipse.snapshot, integrator, start, max_step);
+    for i in 0..=num_steps {
+        if i > 0 {
+            let t = start + (step * i as f64).to_duration();
+            snapshot = simulation::propagate(&snapshot, integrator, t, max_step);
+        }
+        // The axis only grazes the Earth at the ends of the central line, so the end points can
+        // miss because of rounding.
+        if let Some(p) = path_point(&snapshot) {
+            result.push(p);
+        }
+    }
+    result
+}
+
+// Interval used to estimate the motion of the shadow on the ground.
+const MOTION_STEP: Seconds = Seconds(10.0);
+
+fn path_point(snapshot: &Snapshot) -> Option<PathPoint> {
+    let shadow = Shadow::new(snapshot, Body::Moon, Body::Earth);
+    let moon = shadow.occluder;
+    let (distance, central) = surface_intersection(snapshot, &moon, &shadow.axis)?;
+    let umbra_radius = Shadow::umbra_radius(snapshot, Body::Moon, distance);
+
+    // The limits are where the edge of the shadow passes, perpendicular to
+    // the motion of the shadow relative to the ground. We estimate that motion
+    // by moving the bodies in a straight line for a short time.
+    let mut later = *snapshot;
+    later.timestamp = snapshot.timestamp + MOTION_STEP.to_duration();
+    for s in later.bodies.iter_mut() {
+        s.position += s.velocity * MOTION_STEP.0;
+    }
+    let later_shadow = Shadow::new(&later, Body::Moon, Body::Earth);
+    let (_, later_central) =
+        surface_intersection(&later, &later_shadow.occluder, &later_shadow.axis)?;
+    let ground = geo::from_earth_fixed(snapshot, &geo::to_earth_fixed(&later, &later_central));
+    let motion = ground - central;
+    let motion = motion - shadow.axis * motion.dot(&shadow.axis);
+    let mut side = shadow.axis.cross(&motion).normalize() * umbra_radius.abs();
+    if side.dot(&super::earth_north(snapshot)) < 0.0 {
+        side = -side;
+    }
+
+    let limit = |offset: Vector3<f64>| {
+        surface_intersection(snapshot, &(moon + offset), &shadow.axis)
+            .map(|(_, p)| LatLon::from_earth_fixed(&geo::to_earth_fixed(snapshot, &p)))
+    };
+    Some(PathPoint {
+        timestamp: snapshot.timestamp,
+        central: LatLon::from_earth_fixed(&geo::to_earth_fixed(snapshot, &central)),
+        north: limit(side),
+        south: limit(-side),
+        total: umbra_radius > 0.0,
+    })
+}
+
+// Returns the first point where the ray from the origin along the given
+// direction (a unit vector) meets the Earth's surface, along with its distance
+// from the origin.
+fn surface_intersection(
+    snapshot: &Snapshot,
+    origin: &Point3<f64>,
+    direction: &Vector3<f64>,
+) -> Option<(f64, Point3<f64>)> {
+    let o = *origin - snapshot.position(Body::Earth);
+    let b = o.dot(direction);
+    let discriminant = b * b - (o.norm_squared() - Body::Earth.radius64().powi(2));
+    if discriminant < 0.0 {
+        return None;
+    }
+    let distance = -b - discriminant.sqrt();
+    Some((distance, *origin + direction * distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::load_preset, eclipse::find_eclipses};
+
+    #[test]
+    fn total_2017() {
+        let start = load_preset("2017-08-21-solar-eclipse.toml");
+        let end = start.timestamp + chrono::Duration::days(1);
+        let eclipses = find_eclipses(&start, IntegratorKind::Rk4, end, Seconds(60.0));
+        assert_eq!(eclipses.len(), 1);
+        let e = &eclipses[0];
+        let path = &e.path;
+        assert!(path.len() > 150, "{}", path.len());
+        assert!(path.iter().all(|p| p.total));
+
+        // From the northern Pacific, across the United States, to the
+        // Atlantic.
+        assert!(path[0].central.lon < -140.0, "{}", path[0].central);
+        assert!(path[path.len() - 1].central.lon > -50.0);
+
+        // Published values (NASA/Espenak) at greatest eclipse.
+        let p = path
+            .iter()
+            .min_by_key(|p| (p.timestamp - e.greatest).num_seconds().abs())
+            .unwrap();
+        let expected = LatLon::new(36.97, -87.67);
+        assert!(p.central.distance(&expected) < 75.0, "{}", p.central);
+        let width = p.width().unwrap();
+        assert!((width - 115.0).abs() < 10.0, "{}", width);
+        assert!(p.north.unwrap().lat > p.central.lat);
+        assert!(p.south.unwrap().lat < p.central.lat);
+    }
+}
@@ -0,0 +1,128 @@
+// Rendering of a time range to numbered PNG files, e.g. for animations. The
+// simulation moves by a fixed simulated interval per frame, so the output
+// doesn't depend on how fast the machine renders.
+
+use std::{fs, path::PathBuf, rc::Rc};
+
+use chrono::{DateTime, Utc};
+use kiss3d::window::{CanvasSetup, NumSamples, Window};
+
+use crate::{
+    choice::ChoiceSet,
+    config::{CameraSpec, Preset, Session},
+    control::ControlEvent,
+    geo::LatLon,
+    render::{self, Renderer},
+    simulation::{self, IntegratorKind, Seconds, Simulation},
+};
+
+pub struct FrameSequence {
+    pub preset: Preset,
+    // Camera, view, display options and initial state. If not set, they come
+    // from the preset and the defaults of the visualizer.
+    pub session: Option<Session>,
+    // Overrides for the session.
+    pub camera: Option<CameraSpec>,
+    pub observer: Option<LatLon>,
+    pub integrator: Option<IntegratorKind>,
+    // Time of the first frame; the last frame is at or before end.
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    // Simulated time between frames.
+    pub interval: Seconds,
+    // Maximum integration step.
+    pub max_step: Seconds,
+    // Size of the images, in pixels.
+    pub width: u32,
+    pub height: u32,
+    pub out_dir: PathBuf,
+    pub show_ui: bool,
+}
+
+impl FrameSequence {
+    // Opens a window of the requested size and renders the frames to
+    // out_dir/frame-NNNNN.png. Returns a summary. Fails if the window doesn't
+    // get the requested size.
+    pub fn render(&self) -> Result<String, String> {
+        fs::create_dir_all(&self.out_dir)
+            .map_err(|e| format!("{}: {}", self.out_dir.display(), e))?;
+
+        let setup = CanvasSetup {
+            vsync: false,
+            samples: NumSamples::Zero,
+        };
+        let mut window = Window::new_with_setup("planets-rs", self.width, self.height, setup);
+        let preset = ChoiceSet::new([self.preset.clone()]).by_index(0);
+        let mut sim = Simulation::new(
+            &preset,
+            chrono::Duration::hours(1),
+            &ChoiceSet::new(IntegratorKind::ALL).by_index(0),
+        );
+        let cameras = ChoiceSet::new(CameraSpec::ALL);
+        let mut r = Renderer::new(
+            sim.current(),
+            &mut window,
+            &cameras.by_value(self.preset.camera.unwrap_or(CameraSpec::ALL[0])),
+            self.preset.observer.unwrap_or_default(),
+        );
+
+        let mut session = match &self.session {
+            Some(session) => session.clone(),
+            None => {
+                let mut session = Session::capture(&sim, &r);
+                session.camera_view = session.camera.default_view();
+                session
+            }
+        };
+        if let Some(camera) = self.camera {
+            session.camera = camera;
+            session.camera_view = camera.default_view();
+        }
+        if let Some(location) = self.observer {
+            session.observer.location = location;
+        }
+        if let Some(integrator) = self.integrator {
+            session.integrator = integrator;
+        }
+        session.snapshot = simulation::propagate(
+            &session.snapshot,
+            session.integrator,
+            self.start,
+            self.max_step,
+        );
+        sim.restore(&session);
+        // For the eclipse paths.
+        sim.find_eclipses();
+        sim.finish_searches();
+        r.set_snapshot(sim.current());
+        r.handle_event(&ControlEvent::RestoreSession(Rc::new(session)));
+        r.set_show_ui(&window, self.show_ui);
+
+        // The first frame lets the camera pick up the size of the window.
+        r.frame(&mut window, &sim);
+
+        let mut count = 0;
+        loop {
+            let events = r.frame(&mut window, &sim);
+            if events.iter().any(|e| matches!(e, ControlEvent::Exit)) {
+                return Err(format!("interrupted after {} frames", count));
+            }
+            let path = self.out_dir.join(format!("frame-{:05}.png", count));
+            render::write_png(&window, &path, Some((self.width, self.height)))?;
+            count += 1;
+
+            // Computed from the start to avoid accumulating rounding errors.
+            let next = self.start + (self.interval * count as f64).to_duration();
+            if next > self.end {
+                break;
+            }
+            sim.advance_to(next, self.max_step);
+            r.set_snapshot(sim.current());
+        }
+        Ok(format!(
+            "Rendered {} frames to {}\n",
+            count,
+            self.out_dir.display()
+        ))
+    }
+}
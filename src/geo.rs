@@ -0,0 +1,209 @@
+// Geographic coordinates on the Earth's surface.
+//
+// Positions on the Earth are expressed in the Earth-fixed frame: centered on
+// the Earth and rotating with it (see Snapshot::earth_orientation), with the z
+// axis through the north pole and the Greenwich meridian along -x. The Earth
+// is treated as a sphere.
+
+use std::fmt;
+
+use kiss3d::nalgebra::{Point3, Vector3};
+
+use crate::{body::Body, simulation::Snapshot};
+
+// A location on the Earth's surface, in degrees. The longitude is positive
+// east of Greenwich, in the range [-180, 180].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl LatLon {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Self { lat, lon }
+    }
+
+    // Parses a location given as "LAT,LON" in degrees, e.g. "40.71,-74.01".
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let err = || format!("invalid location '{}'", s);
+        let (lat, lon) = s.split_once(',').ok_or_else(err)?;
+        let lat: f64 = lat.trim().parse().map_err(|_| err())?;
+        let lon: f64 = lon.trim().parse().map_err(|_| err())?;
+        if lat.abs() > 90.0 || lon.abs() > 180.0 {
+            return Err(err());
+        }
+        Ok(Self { lat, lon })
+    }
+
+    // Returns the location in the direction of the given vector (in the
+    // Earth-fixed frame).
+    pub fn from_earth_fixed(v: &Vector3<f64>) -> Self {
+        Self {
+            lat: (v.z / v.norm()).asin().to_degrees(),
+            lon: f64::atan2(-v.y, -v.x).to_degrees(),
+        }
+    }
+
+    // Returns the point above the location at the given distance from the
+    // Earth's center, in the Earth-fixed frame.
+    pub fn to_earth_fixed(&self, radius: f64) -> Vector3<f64> {
+        let (lat, lon) = (self.lat.to_radians(), self.lon.to_radians());
+        Vector3::new(-lat.cos() * lon.cos(), -lat.cos() * lon.sin(), lat.sin()) * radius
+    }
+
+    // Great-circle distance to another location, in km.
+    pub fn distance(&self, other: &LatLon) -> f64 {
+        self.to_earth_fixed(1.0).angle(&other.to_earth_fixed(1.0)) * Body::Earth.radius64()
+    }
+}
+
+impl fmt::Display for LatLon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:5.2}°{} {:6.2}°{}",
+            self.lat.abs(),
+            if self.lat >= 0.0 { 'N' } else { 'S' },
+            self.lon.abs(),
+            if self.lon >= 0.0 { 'E' } else { 'W' }
+        )
+    }
+}
+
+// Position in the sky, in degrees: the altitude is above the horizon and the
+// azimuth is measured from the north, towards the east.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Horizontal {
+    pub altitude: f64,
+    pub azimuth: f64,
+}
+
+impl Horizontal {
+    // Returns the position in the sky of the given direction, for an observer
+    // at the given location. The direction is in the Earth-fixed frame.
+    pub fn from_earth_fixed(observer: &LatLon, v: &Vector3<f64>) -> Self {
+        let (up, east, north) = local_axes(observer);
+        let v = v.normalize();
+        Self {
+            altitude: v.dot(&up).asin().to_degrees(),
+            azimuth: f64::atan2(v.dot(&east), v.dot(&north))
+                .to_degrees()
+                .rem_euclid(360.0),
+        }
+    }
+
+    // Returns the unit vector towards this position in the sky, for an
+    // observer at the given location, in the Earth-fixed frame.
+    pub fn to_earth_fixed(&self, observer: &LatLon) -> Vector3<f64> {
+        let (up, east, north) = local_axes(observer);
+        let (alt, az) = (self.altitude.to_radians(), self.azimuth.to_radians());
+        up * alt.sin() + (north * az.cos() + east * az.sin()) * alt.cos()
+    }
+}
+
+impl fmt::Display for Horizontal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "alt {:5.1}° az {:5.1}°", self.altitude, self.azimuth)
+    }
+}
+
+// Returns the up, east and north unit vectors at the given location, in the
+// Earth-fixed frame. At the poles, the axes follow the meridian of the
+// longitude.
+pub fn local_axes(observer: &LatLon) -> (Vector3<f64>, Vector3<f64>, Vector3<f64>) {
+    let up = observer.to_earth_fixed(1.0);
+    // Along increasing longitude; unlike z × up, this is defined at the poles.
+    let lon = observer.lon.to_radians();
+    let east = Vector3::new(lon.sin(), -lon.cos(), 0.0);
+    let north = up.cross(&east);
+    (up, east, north)
+}
+
+// Returns the position of an observer standing at the given location.
+pub fn observer_position(snapshot: &Snapshot, observer: &LatLon) -> Point3<f64> {
+    from_earth_fixed(snapshot, &observer.to_earth_fixed(Body::Earth.radius64()))
+}
+
+// Returns the position in the sky of the given body, for an observer at the
+// given location.
+pub fn horizontal(snapshot: &Snapshot, observer: &LatLon, body: Body) -> Horizontal {
+    let v = to_earth_fixed(snapshot, &snapshot.position(body))
+        - observer.to_earth_fixed(Body::Earth.radius64());
+    Horizontal::from_earth_fixed(observer, &v)
+}
+
+// Converts a position to the Earth-fixed frame.
+pub fn to_earth_fixed(snapshot: &Snapshot, p: &Point3<f64>) -> Vector3<f64> {
+    snapshot.earth_orientation().inverse() * (p - snapshot.position(Body::Earth))
+}
+
+// Converts a vector in the Earth-fixed frame to a position.
+pub fn from_earth_fixed(snapshot: &Snapshot, v: &Vector3<f64>) -> Point3<f64> {
+    snapshot.position(Body::Earth) + snapshot.earth_orientation() * v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lat_lon() {
+        for (lat, lon) in [(0.0, 0.0), (36.97, -87.67), (-45.0, 170.0), (89.0, 12.0)] {
+            let l = LatLon::new(lat, lon);
+            let v = l.to_earth_fixed(Body::Earth.radius64());
+            let back = LatLon::from_earth_fixed(&v);
+            assert!((back.lat - lat).abs() < 1e-9 && (back.lon - lon).abs() < 1e-9);
+        }
+        // Greenwich faces -x and the east is along -y.
+        let v = LatLon::new(0.0, 90.0).to_earth_fixed(1.0);
+        assert!((v - Vector3::new(0.0, -1.0, 0.0)).norm() < 1e-9);
+
+        // One degree along the equator.
+        let d = LatLon::new(0.0, 0.0).distance(&LatLon::new(0.0, 1.0));
+        assert!((d - 111.32).abs() < 0.01, "{}", d);
+        assert_eq!(
+            format!("{}", LatLon::new(36.97, -87.67)),
+            "36.97°N  87.67°W"
+        );
+
+        assert_eq!(
+            LatLon::parse("40.71, -74.01"),
+            Ok(LatLon::new(40.71, -74.01))
+        );
+        assert!(LatLon::parse("40.71").is_err());
+        assert!(LatLon::parse("100,0").is_err());
+    }
+
+    #[test]
+    fn horizontal() {
+        let observer = LatLon::new(40.71, -74.01);
+        let up = observer.to_earth_fixed(1.0);
+        let zenith = Horizontal::from_earth_fixed(&observer, &up);
+        assert!((zenith.altitude - 90.0).abs() < 1e-6);
+
+        let north_pole = Horizontal::from_earth_fixed(&observer, &Vector3::z());
+        assert!((north_pole.altitude - 40.71).abs() < 1e-9);
+        // The azimuth is 0 (or 360 due to rounding).
+        assert!(north_pole.azimuth.min(360.0 - north_pole.azimuth) < 1e-9);
+
+        let h = Horizontal {
+            altitude: 30.0,
+            azimuth: 100.0,
+        };
+        let back = Horizontal::from_earth_fixed(&observer, &h.to_earth_fixed(&observer));
+        assert!((back.altitude - 30.0).abs() < 1e-9 && (back.azimuth - 100.0).abs() < 1e-9);
+
+        // At the poles, the axes are still defined.
+        for lat in [90.0, -90.0] {
+            let pole = LatLon::new(lat, 30.0);
+            let (up, east, north) = local_axes(&pole);
+            assert!((up.cross(&east) - north).norm() < 1e-9);
+            assert!(up.dot(&east).abs() < 1e-9 && north.norm() > 0.999);
+            let back = Horizontal::from_earth_fixed(&pole, &h.to_earth_fixed(&pole));
+            assert!((back.altitude - 30.0).abs() < 1e-9 && (back.azimuth - 100.0).abs() < 1e-9);
+        }
+        let above = Horizontal::from_earth_fixed(&LatLon::new(90.0, 0.0), &Vector3::z());
+        assert!((above.altitude - 90.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,221 @@
+// The Moon as seen from the Earth: phases and illumination, and the events of
+// its orbit.
+//
+// Angles are geocentric (from the center of the Earth) and ignore the light
+// time, which is enough for the phases (the Moon moves by about 0.5 arcsecond
+// during the 1.3 s the light takes to reach the Earth).
+
+use chrono::{DateTime, Utc};
+use kiss3d::nalgebra::Vector3;
+
+use crate::{
+    body::Body,
+    simulation::{self, IntegratorKind, Seconds, Snapshot},
+};
+
+mod orbit;
+mod phases;
+
+pub use orbit::{find_orbit_events, OrbitEvent, OrbitEventKind};
+pub use phases::{find_phases, PhaseEvent};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PhaseName {
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+impl PhaseName {
+    // In order of increasing longitude difference.
+    pub const ALL: [PhaseName; 8] = [
+        PhaseName::New,
+        PhaseName::WaxingCrescent,
+        PhaseName::FirstQuarter,
+        PhaseName::WaxingGibbous,
+        PhaseName::Full,
+        PhaseName::WaningGibbous,
+        PhaseName::LastQuarter,
+        PhaseName::WaningCrescent,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PhaseName::New => "New Moon",
+            PhaseName::WaxingCrescent => "Waxing crescent",
+            PhaseName::FirstQuarter => "First quarter",
+            PhaseName::WaxingGibbous => "Waxing gibbous",
+            PhaseName::Full => "Full Moon",
+            PhaseName::WaningGibbous => "Waning gibbous",
+            PhaseName::LastQuarter => "Last quarter",
+            PhaseName::WaningCrescent => "Waning crescent",
+        }
+    }
+
+    // Returns the phase for a difference of ecliptic longitude between the
+    // Moon and the Sun, in degrees. Each phase covers 45 degrees (about 3.7
+    // days), centered on the principal phases (0, 90, 180 and 270 degrees).
+    pub fn from_longitude_difference(degrees: f64) -> PhaseName {
+        let sector = ((degrees + 22.5) / 45.0).floor().rem_euclid(8.0);
+        PhaseName::ALL[sector as usize]
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MoonPhase {
+    // Angle between the Sun and the Earth seen from the Moon, in degrees: 0 at
+    // full moon, 180 at new moon.
+    pub phase_angle: f64,
+    // Fraction of the Moon's disk that is lit, seen from the Earth.
+    pub illuminated: f64,
+    // Angular distance between the Moon and the Sun seen from the Earth, in
+    // degrees.
+    pub elongation: f64,
+    // Ecliptic longitude of the Moon minus that of the Sun, in degrees in
+    // [0, 360): 0 at new moon, 90 at first quarter, etc.
+    pub longitude_difference: f64,
+    pub name: PhaseName,
+}
+
+impl MoonPhase {
+    pub fn new(snapshot: &Snapshot) -> Self {
+        let earth = snapshot.position(Body::Earth);
+        let sun = snapshot.position(Body::Sun);
+        let moon = snapshot.position(Body::Moon);
+        let phase_angle = (sun - moon).angle(&(earth - moon));
+        let longitude_difference = (longitude(&(moon - earth)) - longitude(&(sun - earth)))
+            .to_degrees()
+            .rem_euclid(360.0);
+        Self {
+            phase_angle: phase_angle.to_degrees(),
+            illuminated: (1.0 + phase_angle.cos()) / 2.0,
+            elongation: (sun - earth).angle(&(moon - earth)).to_degrees(),
+            longitude_difference,
+            name: PhaseName::from_longitude_difference(longitude_difference),
+        }
+    }
+}
+
+// Ecliptic longitude of a vector in the simulation frame, in radians.
+pub fn longitude(v: &Vector3<f64>) -> f64 {
+    f64::atan2(v.y, v.x)
+}
+
+// Events are located to within this precision.
+const PRECISION: Seconds = Seconds(1.0);
+
+// Integrates from the snapshot to end (which can be earlier) and calls f with
+// each pair of consecutive samples, at most step apart. The samples of each
+// pair are in chronological order.
+fn scan(
+    snapshot: &Snapshot,
+    integrator: IntegratorKind,
+    end: DateTime<Utc>,
+    max_step: Seconds,
+    step: Seconds,
+    mut f: impl FnMut(&Snapshot, &Snapshot),
+) {
+    let total = Seconds::from(end - snapshot.timestamp);
+    let num_steps = (total.0.abs() / step.0).ceil() as usize;
+    if num_steps == 0 {
+        return;
+    }
+    let step = total / num_steps as f64;
+    let mut previous = *snapshot;
+    for i in 1..=num_steps {
+        let t = snapshot.timestamp + (step * i as f64).to_duration();
+        let current = simulation::propagate(&previous, integrator, t, max_step);
+        if step.0 > 0.0 {
+            f(&previous, &current);
+        } else {
+            f(&current, &previous);
+        }
+        previous = current;
+    }
+}
+
+// Finds the time between the snapshot time and end when g becomes
+// non-negative, by bisection; g must be negative at the start. Returns the
+// state at that time.
+fn find_crossing(
+    early: &Snapshot,
+    end: DateTime<Utc>,
+    integrator: IntegratorKind,
+    max_step: Seconds,
+    g: impl Fn(&Snapshot) -> f64,
+) -> Snapshot {
+    let propagate = |s: &Snapshot, dt: Seconds| {
+        simulation::propagate(s, integrator, s.timestamp + dt.to_duration(), max_step)
+    };
+    let mut before = *early;
+    let mut step = Seconds::from(end - early.timestamp);
+    while step.0 > PRECISION.0 {
+        step = step * 0.5;
+        let s = propagate(&before, step);
+        if g(&s) < 0.0 {
+            before = s;
+        }
+    }
+    propagate(&before, step * 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::load_preset,
+        simulation::{self, IntegratorKind, Seconds},
+        time::parse_utc,
+    };
+
+    fn phase_at(t: &str) -> MoonPhase {
+        let start = load_preset("2017-08-21-solar-eclipse.toml");
+        let t = parse_utc(t).unwrap();
+        MoonPhase::new(&simulation::propagate(
+            &start,
+            IntegratorKind::Rk4,
+            t,
+            Seconds(60.0),
+        ))
+    }
+
+    #[test]
+    fn principal_phases() {
+        // Published times (USNO) of the phases around the eclipse of
+        // 2017-08-21. The Moon moves by 0.5 degree per hour relative to the
+        // Sun.
+        for (t, longitude, name) in [
+            ("2017-08-07 18:11", 180.0, PhaseName::Full),
+            ("2017-08-15 01:15", 270.0, PhaseName::LastQuarter),
+            ("2017-08-21 18:30", 0.0, PhaseName::New),
+            ("2017-08-29 08:13", 90.0, PhaseName::FirstQuarter),
+        ] {
+            let p = phase_at(t);
+            let diff = (p.longitude_difference - longitude + 180.0).rem_euclid(360.0) - 180.0;
+            assert!(diff.abs() < 0.5, "{}: {:?}", t, p);
+            assert_eq!(p.name, name);
+        }
+
+        let full = phase_at("2017-08-07 18:11");
+        assert!(full.illuminated > 0.99, "{:?}", full);
+        assert!(full.phase_angle < 5.0 && full.elongation > 175.0);
+        let quarter = phase_at("2017-08-29 08:13");
+        assert!((quarter.illuminated - 0.5).abs() < 0.01, "{:?}", quarter);
+        assert!((quarter.elongation - 90.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn names() {
+        let name = PhaseName::from_longitude_difference;
+        assert_eq!(name(359.0), PhaseName::New);
+        assert_eq!(name(30.0), PhaseName::WaxingCrescent);
+        assert_eq!(name(150.0), PhaseName::WaxingGibbous);
+        assert_eq!(name(200.0), PhaseName::Full);
+        assert_eq!(name(300.0), PhaseName::WaningCrescent);
+    }
+}
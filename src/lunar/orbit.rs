@@ -0,0 +1,182 @@
+use chrono::{DateTime, Utc};
+use kiss3d::nalgebra::Vector3;
+
+use crate::{
+    body::Body,
+    simulation::{IntegratorKind, Seconds, Snapshot},
+};
+
+use super::{find_crossing, scan};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum OrbitEventKind {
+    // Closest to the Earth.
+    Perigee,
+    // Farthest from the Earth.
+    Apogee,
+    // The Moon crosses the ecliptic going north.
+    AscendingNode,
+    // The Moon crosses the ecliptic going south.
+    DescendingNode,
+}
+
+impl OrbitEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OrbitEventKind::Perigee => "Perigee",
+            OrbitEventKind::Apogee => "Apogee",
+            OrbitEventKind::AscendingNode => "Ascending node",
+            OrbitEventKind::DescendingNode => "Descending node",
+        }
+    }
+}
+
+// An apsis of the Moon's orbit around the Earth, or a crossing of the
+// ecliptic. Eclipses only happen when the Sun is near one of the nodes at new
+// or full moon.
+#[derive(Copy, Clone, Debug)]
+pub struct OrbitEvent {
+    pub kind: OrbitEventKind,
+    pub timestamp: DateTime<Utc>,
+    // Distance between the centers of the Earth and the Moon, in km.
+    pub distance: f64,
+    // The state of the simulation at the event.
+    pub snapshot: Snapshot,
+}
+
+// Interval between samples when scanning. The apsides and the nodes are each
+// about two weeks apart.
+const SCAN_STEP: Seconds = Seconds(6.0 * 3600.0);
+
+// Position and velocity of the Moon relative to the Earth.
+fn relative_state(snapshot: &Snapshot) -> (Vector3<f64>, Vector3<f64>) {
+    (
+        snapshot.position(Body::Moon) - snapshot.position(Body::Earth),
+        snapshot.velocity(Body::Moon) - snapshot.velocity(Body::Earth),
+    )
+}
+
+// Rate of change of the Earth-Moon distance, in km/s: zero at the apsides.
+fn radial_velocity(snapshot: &Snapshot) -> f64 {
+    let (r, v) = relative_state(snapshot);
+    r.dot(&v) / r.norm()
+}
+
+// Height of the Moon above the ecliptic (of J2000), in km: zero at the nodes.
+fn height(snapshot: &Snapshot) -> f64 {
+    relative_state(snapshot).0.z
+}
+
+// Finds the apsides and the node crossings of the Moon between the snapshot
+// time and the given time (which can be earlier), in chronological order.
+pub fn find_orbit_events(
+    snapshot: &Snapshot,
+    integrator: IntegratorKind,
+    end: DateTime<Utc>,
+    max_step: Seconds,
+) -> Vec<OrbitEvent> {
+    let mut result = Vec::new();
+    scan(
+        snapshot,
+        integrator,
+        end,
+        max_step,
+        SCAN_STEP,
+        |early, late| {
+            let searches: [(fn(&Snapshot) -> f64, OrbitEventKind, OrbitEventKind); 2] = [
+                (
+                    radial_velocity,
+                    OrbitEventKind::Perigee,
+                    OrbitEventKind::Apogee,
+                ),
+                (
+                    height,
+                    OrbitEventKind::AscendingNode,
+                    OrbitEventKind::DescendingNode,
+                ),
+            ];
+            for (f, rising, falling) in searches {
+                let (kind, at) = match (f(early) < 0.0, f(late) < 0.0) {
+                    (true, false) => (
+                        rising,
+                        find_crossing(early, late.timestamp, integrator, max_step, f),
+                    ),
+                    (false, true) => (
+                        falling,
+                        find_crossing(early, late.timestamp, integrator, max_step, |s| -f(s)),
+                    ),
+                    _ => continue,
+                };
+                result.push(OrbitEvent {
+                    kind,
+                    timestamp: at.timestamp,
+                    distance: relative_state(&at).0.norm(),
+                    snapshot: at,
+                });
+            }
+        },
+    );
+    result.sort_by_key(|e| e.timestamp);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::load_preset, time::parse_utc};
+
+    #[test]
+    fn find() {
+        let start = load_preset("2017-08-21-solar-eclipse.toml");
+        let end = parse_utc("2017-10-21 00:00").unwrap();
+        let events = find_orbit_events(&start, IntegratorKind::Rk4, end, Seconds(60.0));
+        let of_kind = |kinds: [OrbitEventKind; 2]| -> Vec<OrbitEvent> {
+            events
+                .iter()
+                .filter(|e| kinds.contains(&e.kind))
+                .copied()
+                .collect()
+        };
+
+        // The anomalistic month is 27.55 days.
+        let apsides = of_kind([OrbitEventKind::Perigee, OrbitEventKind::Apogee]);
+        assert!(apsides.len() == 4 || apsides.len() == 5, "{:#?}", apsides);
+        for (a, b) in apsides.iter().zip(&apsides[1..]) {
+            assert_ne!(a.kind, b.kind);
+            let days = (b.timestamp - a.timestamp).num_hours() as f64 / 24.0;
+            assert!(days > 10.0 && days < 18.0, "{}", days);
+        }
+        for a in &apsides {
+            assert!(radial_velocity(&a.snapshot).abs() < 1e-3, "{:?}", a);
+            let range = match a.kind {
+                OrbitEventKind::Perigee => 355_000.0..372_000.0,
+                _ => 403_000.0..407_500.0,
+            };
+            assert!(range.contains(&a.distance), "{:?}", a);
+        }
+
+        // The draconic month is 27.21 days.
+        let nodes = of_kind([
+            OrbitEventKind::AscendingNode,
+            OrbitEventKind::DescendingNode,
+        ]);
+        assert!(nodes.len() == 4 || nodes.len() == 5, "{:#?}", nodes);
+        for (a, b) in nodes.iter().zip(&nodes[1..]) {
+            assert_ne!(a.kind, b.kind);
+            let days = (b.timestamp - a.timestamp).num_hours() as f64 / 24.0;
+            assert!(days > 12.5 && days < 15.0, "{}", days);
+        }
+        for n in &nodes {
+            // The Moon moves by about 0.1 km/s across the ecliptic.
+            assert!(height(&n.snapshot).abs() < 1.0, "{:?}", n);
+            let (_, v) = relative_state(&n.snapshot);
+            assert_eq!(v.z > 0.0, n.kind == OrbitEventKind::AscendingNode);
+        }
+
+        // The Moon was near a node during the solar eclipse.
+        let eclipse = parse_utc("2017-08-21 18:25").unwrap();
+        assert!(nodes
+            .iter()
+            .any(|n| (n.timestamp - eclipse).num_hours().abs() < 24));
+    }
+}
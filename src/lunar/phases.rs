@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+
+use crate::simulation::{IntegratorKind, Seconds, Snapshot};
+
+use super::{find_crossing, scan, MoonPhase, PhaseName};
+
+// A principal phase of the Moon: new moon, first quarter, full moon or last
+// quarter.
+#[derive(Copy, Clone, Debug)]
+pub struct PhaseEvent {
+    pub name: PhaseName,
+    // When the difference of ecliptic longitude between the Moon and the Sun
+    // is a multiple of 90 degrees.
+    pub timestamp: DateTime<Utc>,
+}
+
+// Interval between samples when scanning for phases. The longitude difference
+// increases by about 3 degrees in this time, always in the same direction.
+const SCAN_STEP: Seconds = Seconds(6.0 * 3600.0);
+
+// Finds the principal phases between the snapshot time and the given time
+// (which can be earlier), in chronological order.
+pub fn find_phases(
+    snapshot: &Snapshot,
+    integrator: IntegratorKind,
+    end: DateTime<Utc>,
+    max_step: Seconds,
+) -> Vec<PhaseEvent> {
+    let mut result = Vec::new();
+    scan(
+        snapshot,
+        integrator,
+        end,
+        max_step,
+        SCAN_STEP,
+        |early, late| {
+            let late_quarter = quarter(late);
+            if quarter(early) == late_quarter {
+                return;
+            }
+            // Negative before the Moon enters the quarter, positive after.
+            let target = late_quarter as f64 * 90.0;
+            let offset = |s: &Snapshot| {
+                (MoonPhase::new(s).longitude_difference - target + 180.0).rem_euclid(360.0) - 180.0
+            };
+            let at = find_crossing(early, late.timestamp, integrator, max_step, offset);
+            result.push(PhaseEvent {
+                name: PhaseName::ALL[2 * late_quarter],
+                timestamp: at.timestamp,
+            });
+        },
+    );
+    result.sort_by_key(|e| e.timestamp);
+    result
+}
+
+// Returns the quarter of the lunation (0 to 3) the Moon is in: 0 between new
+// moon and first quarter, etc.
+fn quarter(snapshot: &Snapshot) -> usize {
+    (MoonPhase::new(snapshot).longitude_difference / 90.0) as usize % 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::load_preset, simulation, time::parse_utc};
+
+    #[test]
+    fn find() {
+        let start = load_preset("2017-08-21-solar-eclipse.toml");
+        let end = parse_utc("2017-09-30 00:00").unwrap();
+        let phases = find_phases(&start, IntegratorKind::Rk4, end, Seconds(60.0));
+
+        // Published times (USNO).
+        let expected = [
+            ("2017-08-21 18:30", PhaseName::New),
+            ("2017-08-29 08:13", PhaseName::FirstQuarter),
+            ("2017-09-06 07:03", PhaseName::Full),
+            ("2017-09-13 06:25", PhaseName::LastQuarter),
+            ("2017-09-20 05:30", PhaseName::New),
+            ("2017-09-28 02:53", PhaseName::FirstQuarter),
+        ];
+        assert_eq!(phases.len(), expected.len(), "{:#?}", phases);
+        for (p, (time, name)) in phases.iter().zip(expected) {
+            assert_eq!(p.name, name);
+            let dt = Seconds::from(p.timestamp - parse_utc(time).unwrap());
+            assert!(dt.0.abs() < 300.0, "{}: {:?}", time, dt);
+        }
+
+        // Searching backwards gives the same results.
+        let back = find_phases(
+            &simulation::propagate(&start, IntegratorKind::Rk4, end, Seconds(60.0)),
+            IntegratorKind::Rk4,
+            start.timestamp,
+            Seconds(60.0),
+        );
+        assert_eq!(back.len(), phases.len());
+        for (a, b) in back.iter().zip(&phases) {
+            assert_eq!(a.name, b.name);
+            assert!((a.timestamp - b.timestamp).num_seconds().abs() < 5);
+        }
+    }
+}
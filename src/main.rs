@@ -1,4 +1,6 @@
-use config::Config;
+use std::{path::Path, rc::Rc};
+
+use config::{Config, Session};
 use control::ControlEvent;
 use kiss3d::{
     nalgebra::{Point2, Point3},
@@ -10,13 +12,33 @@ use simulation::Simulation;
 
 mod body;
 mod choice;
+mod cli;
 mod config;
 mod control;
+mod eclipse;
+mod frames;
+mod geo;
+mod lunar;
 mod render;
 mod simulation;
 mod state;
+mod time;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        // Headless mode.
+        std::process::exit(cli::run(&args));
+    }
+
+    let config = match Config::load(Path::new(Config::PRESETS_DIR)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
     let setup = CanvasSetup {
         vsync: true,
         samples: NumSamples::Zero,
@@ -31,17 +53,41 @@ fn main() {
         &Point3::new(0.8, 0.8, 0.8),
     );
     window.render();
-    let config = Config::default();
-    let mut sim = Simulation::new(&config.initial_preset, &config.initial_speed);
-    let mut r = Renderer::new(sim.current(), &mut window, &config.initial_camera);
+    let mut sim = Simulation::new(
+        &config.initial_preset,
+        config.initial_speed,
+        &config.initial_integrator,
+    );
+    let mut r = Renderer::new(
+        sim.current(),
+        &mut window,
+        &config.initial_camera,
+        config.initial_preset.observer.unwrap_or_default(),
+    );
 
     loop {
         let events = r.frame(&mut window, &sim);
 
         for event in events {
-            if matches!(event, ControlEvent::Exit) {
-                return;
-            }
+            let session_file = Path::new(Config::SESSION_FILE);
+            let event = match event {
+                ControlEvent::Exit => return,
+                ControlEvent::SaveSession => {
+                    match Session::capture(&sim, &r).save(session_file) {
+                        Ok(()) => println!("Session saved to {}", session_file.display()),
+                        Err(err) => eprintln!("Error: {}", err),
+                    }
+                    continue;
+                }
+                ControlEvent::LoadSession => match Session::load(session_file) {
+                    Ok(session) => ControlEvent::RestoreSession(Rc::new(session)),
+                    Err(err) => {
+                        eprintln!("Error: {}", err);
+                        continue;
+                    }
+                },
+                event => event,
+            };
             r.handle_event(&event);
             sim.handle_event(&event);
         }
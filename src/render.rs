@@ -1,8 +1,10 @@
 use self::body_hint::render_body_hint;
 use self::body_material::*;
 use self::camera::*;
+use self::eclipse_path::EclipsePath;
 use self::grid::Grid;
 use self::lines_material::LinesMaterial;
+use self::orbit_markers::render_orbit_markers;
 use self::trail::Trail;
 use self::ui::Ui;
 
@@ -11,7 +13,13 @@ use crate::body::Body::*;
 use crate::choice::Choice;
 use crate::config::CameraDirection;
 use crate::config::CameraSpec;
+use crate::config::CameraView;
+use crate::config::Config;
+use crate::config::LookAt;
+use crate::config::Observer;
 use crate::control::ControlEvent;
+use crate::eclipse;
+use crate::geo;
 use crate::render::flat_material::FlatMaterial;
 use crate::render::fps::Fps;
 use crate::render::skybox::Skybox;
@@ -39,21 +47,28 @@ use std::{cell::RefCell, rc::Rc};
 mod body_hint;
 mod body_material;
 mod camera;
+mod eclipse_path;
 mod flat_material;
 mod fps;
 mod grid;
 mod interpolate;
 mod lines_material;
+mod orbit_markers;
+mod screenshot;
 mod skybox;
 mod trail;
 mod ui;
 
+pub use screenshot::write_png;
+
 pub struct Renderer {
     camera: MyCamera,
 
     camera_spec: Choice<CameraSpec>,
+    observer: Observer,
 
     grid: Grid,
+    horizon: SceneNode,
     skybox: Skybox,
 
     sun_node: SceneNode,
@@ -67,6 +82,7 @@ pub struct Renderer {
     earth_normal_texture: Rc<Texture>,
     earth_axis: SceneNode,
     earth_trail: Trail,
+    eclipse_path: EclipsePath,
 
     moon_node: SceneNode,
     moon_lighting: Rc<RefCell<BodyLightingData>>,
@@ -74,6 +90,10 @@ pub struct Renderer {
 
     fps: Fps,
     ui: Ui,
+    show_ui: bool,
+    show_orbit_markers: bool,
+    // Set when a screenshot is requested; true to include the UI.
+    pending_screenshot: Option<bool>,
 
     snapshot: Snapshot,
 }
@@ -83,6 +103,7 @@ impl Renderer {
         snapshot: Snapshot,
         window: &mut Window,
         initial_camera: &Choice<CameraSpec>,
+        initial_observer: Observer,
     ) -> Self {
         TextureManager::get_global_manager(|tm| tm.set_generate_mipmaps(true));
         Self::init_materials();
@@ -133,6 +154,8 @@ impl Renderer {
             Earth.color4(0.6),
         );
 
+        let eclipse_path = EclipsePath::new(window);
+
         // Init the Moon. The moon also uses our custom body material.
         let mut moon_node = window.add_sphere(Moon.radius());
         moon_node.set_material(MaterialManager::get_global_manager(|m| {
@@ -166,12 +189,21 @@ impl Renderer {
         let camera = MyCamera::new(-Ui::WIDTH * window.scale_factor());
         let skybox = Skybox::new(window, 4e+10);
         let grid = Grid::new(window, 20);
+
+        // The horizon plane for the observer camera: a large disk slightly
+        // below the observer, beyond the near clipping plane. It hides
+        // everything below the horizon.
+        let mut horizon = window.add_cylinder(Self::HORIZON_RADIUS as f32, 1.0);
+        horizon.set_color(0.08, 0.1, 0.06);
+        horizon.set_visible(false);
         let ui = Ui::new(window);
 
         let mut renderer = Renderer {
             camera,
             camera_spec: initial_camera.clone(),
+            observer: initial_observer,
             grid,
+            horizon,
             skybox,
             sun_node,
             earth_node,
@@ -183,11 +215,15 @@ impl Renderer {
             earth_normal_texture,
             earth_axis,
             earth_trail,
+            eclipse_path,
             moon_node,
             moon_lighting,
             moon_trail,
             fps: Fps::new(),
             ui,
+            show_ui: true,
+            show_orbit_markers: true,
+            pending_screenshot: None,
             snapshot,
         };
 
@@ -226,6 +262,18 @@ impl Renderer {
         TextureManager::get_global_manager(|tm| tm.add(Path::new(&path), file))
     }
 
+    // Shows or hides the UI panel. The scene is centered in the window when
+    // the panel is hidden.
+    pub fn set_show_ui(&mut self, window: &Window, show: bool) {
+        self.show_ui = show;
+        let dx_px = if show {
+            -Ui::WIDTH * window.scale_factor()
+        } else {
+            0.0
+        };
+        self.camera.set_dx_px(dx_px);
+    }
+
     pub fn set_snapshot(&mut self, snapshot: Snapshot) {
         self.snapshot = snapshot
     }
@@ -237,8 +285,13 @@ impl Renderer {
         sim_state: &dyn SimulationState,
     ) -> Vec<ControlEvent> {
         let cam_spec = self.camera_spec.get();
-        let (focus, eye_dir) = self.focus_and_eye_dir(&cam_spec);
-        self.camera.update(focus, eye_dir);
+        if cam_spec.direction == CameraDirection::FromSurface {
+            self.update_observer_camera();
+        } else {
+            let (focus, eye_dir) = self.focus_and_eye_dir(&cam_spec);
+            self.camera.update(focus, eye_dir);
+        }
+        self.horizon.set_visible(self.camera.is_observer());
 
         self.grid.update(
             Point3::new(0.0, 0.0, -self.camera.focus().z as f32),
@@ -279,6 +332,14 @@ impl Renderer {
         self.earth_trail
             .frame(self.abs_position(Earth), self.camera.focus());
 
+        self.eclipse_path.frame(
+            eclipse::nearest(sim_state.eclipses(), sim_state.timestamp()),
+            Isometry3::from_parts(
+                earth_transformation.translation,
+                nalgebra::convert(self.snapshot.earth_orientation()),
+            ),
+        );
+
         // Moon.
         self.moon_node
             .set_local_transformation(self.transformation(Moon));
@@ -296,12 +357,34 @@ impl Renderer {
         for body in [Sun, Earth, Moon] {
             render_body_hint(body, self.render_position(body), &self.camera, window);
         }
+        if self.show_orbit_markers {
+            render_orbit_markers(
+                sim_state.orbit_events(),
+                sim_state.timestamp(),
+                self.camera.focus(),
+                &self.camera,
+                window,
+            );
+        }
 
         self.fps.frame();
-        let mut events = self.ui.frame(window, sim_state, &*self);
+        let screenshot = self.pending_screenshot.take();
+        let mut events = if !self.show_ui || screenshot == Some(false) {
+            window.conrod_ui_mut().set_widgets();
+            Vec::new()
+        } else {
+            self.ui.frame(window, sim_state, &*self)
+        };
         if !window.render_with_camera(&mut self.camera) {
             return vec![ControlEvent::Exit];
         }
+        if screenshot.is_some() {
+            let dir = Path::new(Config::SCREENSHOT_DIR);
+            match screenshot::save(window, dir, sim_state.timestamp()) {
+                Ok(path) => println!("Screenshot saved to {}", path.display()),
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
         for mut event in window.events().iter() {
             if let Some(ev) = ControlEvent::from_window_event(&mut event, sim_state) {
                 events.push(ev);
@@ -310,7 +393,47 @@ impl Renderer {
         events
     }
 
+    // Distance of the horizon plane below the observer, and its radius (km).
+    const HORIZON_DEPTH: f64 = 1e3;
+    const HORIZON_RADIUS: f64 = 1e7;
+
+    // Places the camera on the Earth's surface, at the observer location.
+    fn update_observer_camera(&mut self) {
+        let location = self.observer.location;
+        let orientation = self.snapshot.earth_orientation();
+        let (up, _, north) = geo::local_axes(&location);
+        let (up, north) = (orientation * up, orientation * north);
+        let eye = geo::observer_position(&self.snapshot, &location);
+        let target = match self.observer.look_at {
+            LookAt::Body(body) => geo::horizontal(&self.snapshot, &location, body),
+            LookAt::Sky(h) => h,
+        };
+        self.camera.update_observer(
+            eye,
+            up,
+            north,
+            target.altitude.to_radians(),
+            target.azimuth.to_radians(),
+        );
+
+        // The cylinder axis is along y.
+        let pos: Point3<f32> =
+            nalgebra::convert(eye - self.camera.focus().coords - up * Self::HORIZON_DEPTH);
+        let rotation: UnitQuaternion<f32> = nalgebra::convert(
+            UnitQuaternion::rotation_between(&Vector3::y(), &up)
+                .unwrap_or_else(UnitQuaternion::identity),
+        );
+        self.horizon.set_local_transformation(Isometry3::from_parts(
+            Translation3::new(pos.x, pos.y, pos.z),
+            rotation,
+        ));
+    }
+
     fn transition_camera(&mut self, spec: &CameraSpec) {
+        if spec.direction == CameraDirection::FromSurface {
+            // The observer camera is placed directly, without a transition.
+            return;
+        }
         let body = spec.focus;
         let (focus, eye_dir) = self.focus_and_eye_dir(spec);
         let radius = body.radius64();
@@ -332,9 +455,37 @@ impl Renderer {
                 self.camera_spec = camera_focus.clone();
                 self.transition_camera(&self.camera_spec.get());
             }
-            ControlEvent::Reverse | ControlEvent::LoadPreset(..) => {
+            ControlEvent::Reverse | ControlEvent::SeekTo(..) => {
+                self.earth_trail.reset();
+                self.moon_trail.reset();
+            }
+            ControlEvent::LoadPreset(preset) => {
                 self.earth_trail.reset();
                 self.moon_trail.reset();
+                if let Some(observer) = preset.observer {
+                    self.observer = observer;
+                }
+                if let Some(camera) = preset.camera {
+                    self.camera_spec = self.camera_spec.choice_set().by_value(camera);
+                    self.transition_camera(&camera);
+                }
+            }
+            ControlEvent::RestoreSession(session) => {
+                self.earth_trail.reset();
+                self.moon_trail.reset();
+                self.observer = session.observer;
+                self.camera_spec = self.camera_spec.choice_set().by_value(session.camera);
+                let min_dist = session.camera.focus.radius64() * 1.5;
+                self.camera.set_view(session.camera_view, min_dist);
+
+                let show = session.toggles;
+                self.earth_trail.set_visible(show.trails);
+                self.moon_trail.set_visible(show.trails);
+                self.grid.set_visible(show.ecliptic);
+                self.skybox.set_visible(show.skybox);
+                self.earth_axis.set_visible(show.earth_axis);
+                self.eclipse_path.set_visible(show.eclipse_path);
+                self.show_orbit_markers = show.orbit_markers;
             }
             ControlEvent::ToggleTrails => {
                 let visible = !self.earth_trail.is_visible();
@@ -350,9 +501,19 @@ impl Renderer {
             ControlEvent::ToggleEarthAxis => {
                 self.earth_axis.set_visible(!self.earth_axis.is_visible());
             }
+            ControlEvent::ToggleEclipsePath => {
+                self.eclipse_path
+                    .set_visible(!self.eclipse_path.is_visible());
+            }
+            ControlEvent::ToggleOrbitMarkers => {
+                self.show_orbit_markers = !self.show_orbit_markers;
+            }
             ControlEvent::ToggleHelp => {
                 self.ui.show_help = !self.ui.show_help;
             }
+            ControlEvent::Screenshot { show_ui } => {
+                self.pending_screenshot = Some(*show_ui);
+            }
             _ => {}
         }
     }
@@ -362,11 +523,7 @@ impl Renderer {
     }
 
     pub fn abs_position(&self, body: Body) -> Point3<f64> {
-        match body {
-            Sun => Point3::default(),
-            Earth => self.snapshot.earth_position,
-            Moon => self.snapshot.moon_position,
-        }
+        self.snapshot.position(body)
     }
 
     fn focus_and_eye_dir(&self, cam_spec: &CameraSpec) -> (Point3<f64>, Vector3<f64>) {
@@ -374,6 +531,8 @@ impl Renderer {
         let eye_dir = match cam_spec.direction {
             CameraDirection::FromAbove => Vector3::z_axis().into_inner(),
             CameraDirection::FromBody(b) => self.abs_position(b) - focus,
+            // Not used: the observer camera is placed by update_observer_camera.
+            CameraDirection::FromSurface => Vector3::z_axis().into_inner(),
         };
         (focus, eye_dir)
     }
@@ -408,6 +567,14 @@ impl RenderState for Renderer {
         self.camera_spec.clone()
     }
 
+    fn camera_view(&self) -> CameraView {
+        self.camera.view()
+    }
+
+    fn observer(&self) -> Observer {
+        self.observer
+    }
+
     fn show_trails(&self) -> bool {
         self.earth_trail.is_visible()
     }
@@ -424,6 +591,13 @@ impl RenderState for Renderer {
         self.earth_axis.is_visible()
     }
 
+    fn show_eclipse_path(&self) -> bool {
+        self.eclipse_path.is_visible()
+    }
+
+    fn show_orbit_markers(&self) -> bool {
+        self.show_orbit_markers
+    }
     fn show_help(&self) -> bool {
         self.ui.show_help
     }
@@ -14,6 +14,8 @@ use kiss3d::{
     window::Canvas,
 };
 
+use crate::config::CameraView;
+
 use super::interpolate;
 
 pub struct MyCamera {
@@ -53,6 +55,24 @@ pub struct MyCamera {
     last_framebuffer_size: Vector2<u32>,
 
     transition: Option<TransitionState>,
+
+    // Set when the camera stands on a surface (see update_observer).
+    observer: Option<ObserverView>,
+
+    // Applied on the next update (see set_view).
+    view_next_frame: Option<CameraView>,
+}
+
+// In observer mode, the eye is at the focus and looks in a direction given
+// in the local horizontal frame. The user rotations change the azimuth (yaw)
+// and altitude (pitch) of the view, and scrolling changes the field of view.
+struct ObserverView {
+    // Local vertical and north directions.
+    up: Vector3<f64>,
+    north: Vector3<f64>,
+    // Altitude and azimuth of the view direction, in radians.
+    altitude: f64,
+    azimuth: f64,
 }
 
 struct TransitionState {
@@ -76,14 +96,15 @@ impl MyCamera {
     const TRANSITION_TIME: Duration = Duration::from_millis(500);
     const TRANSITION_SIGMOID_K: f64 = 5.0;
     const OVERHEAD_DIST: f64 = 5e+8;
+    const FOV: f64 = CameraView::DEFAULT_FOV;
+    const MIN_OBSERVER_FOV: f64 = 0.2 * PI / 180.0;
 
     pub fn new(dx_px: f64) -> Self {
-        let fov = std::f64::consts::PI / 4.0;
         let aspect = 800.0 / 600.0;
         let (znear, zfar) = (5e+2, 2e+10);
 
         let mut res = Self {
-            projection: Perspective3::new(aspect, fov, znear, zfar),
+            projection: Perspective3::new(aspect, Self::FOV, znear, zfar),
             dx_px,
             proj: nalgebra::zero(),
             view: nalgebra::zero(),
@@ -102,13 +123,26 @@ impl MyCamera {
             last_cursor_pos: nalgebra::zero(),
             last_framebuffer_size: Vector2::new(800, 600),
             transition: None,
+            observer: None,
+            view_next_frame: None,
         };
         res.calc_matrices();
         res
     }
 
     pub fn update(&mut self, focus: Point3<f64>, eye_dir: Vector3<f64>) {
+        self.leave_observer();
         let (pitch, yaw) = Self::pitch_and_yaw(eye_dir);
+        if let Some(view) = self.view_next_frame.take() {
+            self.transition = None;
+            self.dist = view.distance.clamp(self.min_dist, self.max_dist);
+            self.pitch = pitch;
+            self.yaw = yaw;
+            self.user_pitch = view.pitch;
+            self.user_yaw = view.yaw;
+            self.enforce_pitch_limits();
+            self.calc_matrices();
+        }
         if let Some(scale) = self.dist_scale_next_frame {
             self.dist = (self.dist * scale).clamp(self.min_dist, self.max_dist);
             self.dist_scale_next_frame = None;
@@ -195,6 +229,58 @@ impl MyCamera {
         }
     }
 
+    // Places the eye at the given point, looking towards the given altitude
+    // and azimuth (in radians) in the local horizontal frame defined by the
+    // up and north directions. Must be called every frame while the camera
+    // stands on a surface; calling update() or transition_to() leaves this
+    // mode.
+    pub fn update_observer(
+        &mut self,
+        eye: Point3<f64>,
+        up: Vector3<f64>,
+        north: Vector3<f64>,
+        altitude: f64,
+        azimuth: f64,
+    ) {
+        if self.observer.is_none() {
+            self.transition = None;
+            self.user_pitch = 0.0;
+            self.user_yaw = 0.0;
+        }
+        if let Some(view) = self.view_next_frame.take() {
+            self.user_pitch = view.pitch;
+            self.user_yaw = view.yaw;
+            self.projection
+                .set_fovy(view.fov.clamp(Self::MIN_OBSERVER_FOV, Self::FOV));
+        }
+        if let Some(scale) = self.dist_scale_next_frame.take() {
+            let fov = self.projection.fovy() * scale;
+            self.projection
+                .set_fovy(fov.clamp(Self::MIN_OBSERVER_FOV, Self::FOV));
+        }
+        self.focus = eye;
+        self.observer = Some(ObserverView {
+            up,
+            north,
+            altitude,
+            azimuth,
+        });
+        self.calc_matrices();
+    }
+
+    pub fn is_observer(&self) -> bool {
+        self.observer.is_some()
+    }
+
+    // Leaves observer mode, if set.
+    fn leave_observer(&mut self) {
+        if self.observer.take().is_some() {
+            self.user_pitch = 0.0;
+            self.user_yaw = 0.0;
+            self.projection.set_fovy(Self::FOV);
+        }
+    }
+
     pub fn transition_to(
         &mut self,
         focus: Point3<f64>,
@@ -202,6 +288,8 @@ impl MyCamera {
         dist: f64,
         min_dist: f64,
     ) {
+        self.leave_observer();
+
         // Calculate a distance from which both bodies would be visible (in their current positions).
         // We will first zoom out to that distance.
         let mut mid_dist = 4.0 * (self.focus - focus).norm();
@@ -239,6 +327,31 @@ impl MyCamera {
         })
     }
 
+    // Returns the distance and the user rotations (or the field of view, in
+    // observer mode), which are not given by the camera spec.
+    pub fn view(&self) -> CameraView {
+        CameraView {
+            distance: self.dist,
+            pitch: self.user_pitch,
+            yaw: self.user_yaw,
+            fov: self.projection.fovy(),
+        }
+    }
+
+    // Restores a view returned by view(), without a transition. It is applied
+    // by the next call to update() or update_observer().
+    pub fn set_view(&mut self, view: CameraView, min_dist: f64) {
+        self.min_dist = min_dist;
+        self.view_next_frame = Some(view);
+    }
+
+    // Sets the horizontal offset of the center of the view, in pixels (to
+    // leave room for the UI panel).
+    pub fn set_dx_px(&mut self, dx_px: f64) {
+        self.dx_px = dx_px;
+        self.calc_matrices();
+    }
+
     pub fn focus(&self) -> Point3<f64> {
         self.focus
     }
@@ -267,12 +380,26 @@ impl MyCamera {
     }
 
     pub fn view_transform_64(&self) -> Isometry3<f64> {
+        if let Some(o) = &self.observer {
+            let dir = self.observer_look_dir(o);
+            return Isometry3::look_at_rh(&Point3::origin(), &Point3::from(dir), &o.up);
+        }
         Isometry3::from_parts(
             Translation3::new(0.0, 0.0, -self.dist),
             self.rotation().inverse(),
         )
     }
 
+    // Returns the view direction in observer mode.
+    fn observer_look_dir(&self, o: &ObserverView) -> Vector3<f64> {
+        // Stay clear of the zenith and nadir, where the view is not defined.
+        let limit = 0.49 * PI;
+        let altitude = (o.altitude + self.user_pitch).clamp(-limit, limit);
+        let azimuth = o.azimuth + self.user_yaw;
+        let east = o.north.cross(&o.up);
+        o.up * altitude.sin() + (o.north * azimuth.cos() + east * azimuth.sin()) * altitude.cos()
+    }
+
     const SCROLL_STEP: f64 = 0.99;
     const YAW_STEP: f64 = 0.005;
     const PITCH_STEP: f64 = 0.005;
@@ -287,6 +414,14 @@ impl MyCamera {
         if self.transition.is_some() {
             return;
         }
+        if self.observer.is_some() {
+            // Drag the sky; slower when zoomed in.
+            let scale = self.projection.fovy() / Self::FOV;
+            self.user_yaw -= dpos.x * Self::YAW_STEP * scale;
+            self.user_pitch += dpos.y * Self::PITCH_STEP * scale;
+            self.calc_matrices();
+            return;
+        }
         self.user_yaw += dpos.x * Self::YAW_STEP;
         self.user_pitch -= dpos.y * Self::PITCH_STEP;
         self.enforce_pitch_limits();
@@ -344,6 +479,9 @@ impl Camera for MyCamera {
 
     // eye is the camera eye, relative to the focus.
     fn eye(&self) -> Point3<f32> {
+        if self.observer.is_some() {
+            return Point3::origin();
+        }
         nalgebra::convert(self.rotation() * Point3::new(0.0, 0.0, self.dist))
     }
 
@@ -413,4 +551,47 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn observer() {
+        let mut camera = MyCamera::new(0.0);
+        let eye = Point3::new(1e8, 2e8, 3e3);
+        let (up, north) = (Vector3::x(), Vector3::z());
+        // Looking east (+y, since east = north x up), 30 degrees up.
+        camera.update_observer(eye, up, north, PI / 6.0, PI / 2.0);
+        assert!(camera.is_observer());
+        assert_eq!(camera.focus(), eye);
+        assert_eq!(camera.eye(), Point3::origin());
+
+        // The view direction is along -z in view coordinates.
+        let target = Vector3::new(0.5, (0.75f64).sqrt(), 0.0);
+        let v = camera.view_transform_64() * target;
+        assert!((v - Vector3::new(0.0, 0.0, -1.0)).norm() < 1e-9, "{}", v);
+
+        camera.update(eye, Vector3::z());
+        assert!(!camera.is_observer());
+    }
+
+    #[test]
+    fn set_view() {
+        let mut camera = MyCamera::new(0.0);
+        let view = CameraView {
+            distance: 1e6,
+            pitch: 0.5,
+            yaw: -1.0,
+            fov: MyCamera::FOV,
+        };
+        camera.transition_to(Point3::origin(), Vector3::x(), 1e5, 1e4);
+        camera.set_view(view, 1e4);
+        // The transition is cancelled.
+        camera.update(Point3::origin(), Vector3::x());
+        assert_eq!(camera.view(), view);
+
+        // In observer mode, the angles apply to the look direction.
+        let observer_view = CameraView { fov: 0.1, ..view };
+        camera.set_view(observer_view, 1e4);
+        camera.update_observer(Point3::origin(), Vector3::x(), Vector3::z(), 0.0, 0.0);
+        let v = camera.view();
+        assert_eq!((v.pitch, v.yaw, v.fov), (0.5, -1.0, 0.1));
+    }
 }
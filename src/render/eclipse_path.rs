@@ -0,0 +1,117 @@
+use std::{cell::RefCell, rc::Rc};
+
+use chrono::{DateTime, Utc};
+use kiss3d::{
+    nalgebra::{Isometry3, Point2, Point3, Point4, Vector3},
+    resource::{AllocationType, MaterialManager, Mesh},
+    scene::SceneNode,
+    window::Window,
+};
+
+use super::lines_material::LinesData;
+use crate::{body::Body, eclipse::Eclipse, geo::LatLon};
+
+// Overlay that draws the ground track of a central solar eclipse on the Earth:
+// the central line and the northern and southern limits.
+pub struct EclipsePath {
+    scene_node: SceneNode,
+    lines_data: Rc<RefCell<LinesData>>,
+    // Time of greatest eclipse of the eclipse currently shown.
+    current: Option<DateTime<Utc>>,
+}
+
+// The lines are drawn slightly above the surface, so they are not hidden by
+// the Earth mesh.
+const ALTITUDE_SCALE: f64 = 1.003;
+
+impl EclipsePath {
+    pub fn new(window: &mut Window) -> Self {
+        // We add an object with an empty mesh, then we associate it with the
+        // lines data and material.
+        let mut node = window.add_mesh(
+            Rc::new(RefCell::new(Mesh::new(
+                Vec::new(),
+                Vec::new(),
+                None,
+                None,
+                false,
+            ))),
+            Vector3::new(1.0, 1.0, 1.0),
+        );
+        node.set_material(MaterialManager::get_global_manager(|m| {
+            m.get("lines").unwrap()
+        }));
+        let lines_data = Rc::new(RefCell::new(LinesData::new(
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            AllocationType::DynamicDraw,
+        )));
+        node.data_mut()
+            .get_object_mut()
+            .set_user_data(Box::new(Rc::clone(&lines_data)));
+
+        Self {
+            scene_node: node,
+            lines_data,
+            current: None,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.scene_node.is_visible()
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.scene_node.set_visible(visible);
+    }
+
+    // Shows the path of the given eclipse (if any). The transformation places
+    // the Earth-fixed frame in the scene.
+    pub fn frame(&mut self, eclipse: Option<&Eclipse>, earth_frame: Isometry3<f32>) {
+        self.scene_node.set_local_transformation(earth_frame);
+        let greatest = eclipse.map(|e| e.greatest);
+        if greatest == self.current {
+            return;
+        }
+        self.current = greatest;
+
+        let mut lines_data = self.lines_data.borrow_mut();
+        // Save the reference to allow mutable borrows of multiple struct fields.
+        let lines_data = &mut *lines_data;
+        let coords = lines_data.coords.data_mut().as_mut().unwrap();
+        let colors = lines_data.colors.data_mut().as_mut().unwrap();
+        let edges = lines_data.edges.data_mut().as_mut().unwrap();
+        coords.clear();
+        colors.clear();
+        edges.clear();
+
+        let path = eclipse.map_or(&[][..], |e| &e.path);
+        let central_color = Point4::new(1.0, 0.3, 0.2, 0.9);
+        let limit_color = Point4::new(1.0, 0.7, 0.2, 0.7);
+        let lines: [(Vec<Option<LatLon>>, Point4<f32>); 3] = [
+            (
+                path.iter().map(|p| Some(p.central)).collect(),
+                central_color,
+            ),
+            (path.iter().map(|p| p.north).collect(), limit_color),
+            (path.iter().map(|p| p.south).collect(), limit_color),
+        ];
+        let radius = Body::Earth.radius64() * ALTITUDE_SCALE;
+        for (points, color) in lines {
+            let mut prev: Option<u16> = None;
+            for point in points {
+                prev = point.map(|p| {
+                    let idx = coords.len() as u16;
+                    let v = p.to_earth_fixed(radius);
+                    coords.push(Point3::new(v.x as f32, v.y as f32, v.z as f32));
+                    colors.push(color);
+                    if let Some(prev) = prev {
+                        edges.push(Point2::new(prev, idx));
+                    }
+                    idx
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use kiss3d::nalgebra::{self, Point2, Point3, Vector2};
+use kiss3d::{camera::Camera, window::Window};
+
+use crate::body::Body;
+use crate::lunar::{OrbitEvent, OrbitEventKind};
+
+// Draws markers at the positions of the Moon at the orbit events that are
+// already past, which are along its trail: squares at the apsides and
+// triangles at the nodes (pointing north for the ascending node).
+pub fn render_orbit_markers(
+    events: &[OrbitEvent],
+    timestamp: DateTime<Utc>,
+    focus: Point3<f64>,
+    camera: &dyn Camera,
+    window: &mut Window,
+) {
+    for e in events.iter().filter(|e| e.timestamp <= timestamp) {
+        let pos: Point3<f32> = nalgebra::convert(e.snapshot.position(Body::Moon) - focus.coords);
+        let projected =
+            Point3::from_homogeneous(camera.transformation() * pos.to_homogeneous()).unwrap();
+        if projected.z > 1.0 {
+            // Behind us.
+            continue;
+        }
+        let scale = 0.5 / window.scale_factor() as f32;
+        let point = Point2::new(
+            projected.x * window.width() as f32 * scale,
+            projected.y * window.height() as f32 * scale,
+        );
+
+        const SIZE: f32 = 5.0;
+        let (corners, color): (&[Vector2<f32>], _) = match e.kind {
+            OrbitEventKind::Perigee | OrbitEventKind::Apogee => (
+                &[
+                    Vector2::new(-SIZE, -SIZE),
+                    Vector2::new(SIZE, -SIZE),
+                    Vector2::new(SIZE, SIZE),
+                    Vector2::new(-SIZE, SIZE),
+                ],
+                Point3::new(0.9, 0.9, 0.9),
+            ),
+            OrbitEventKind::AscendingNode => (
+                &[
+                    Vector2::new(-SIZE, -SIZE),
+                    Vector2::new(SIZE, -SIZE),
+                    Vector2::new(0.0, SIZE),
+                ],
+                Point3::new(0.3, 0.8, 1.0),
+            ),
+            OrbitEventKind::DescendingNode => (
+                &[
+                    Vector2::new(-SIZE, SIZE),
+                    Vector2::new(SIZE, SIZE),
+                    Vector2::new(0.0, -SIZE),
+                ],
+                Point3::new(0.3, 0.8, 1.0),
+            ),
+        };
+        for (i, a) in corners.iter().enumerate() {
+            let b = corners[(i + 1) % corners.len()];
+            window.draw_planar_line(&(point + *a), &(point + b), &color);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use kiss3d::window::Window;
+
+// Saves the last rendered frame as a PNG file in the given directory, named
+// after the simulation time. Returns the path of the file.
+pub fn save(window: &Window, dir: &Path, timestamp: DateTime<Utc>) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let path = unique_path(dir, timestamp);
+    write_png(window, &path, None)?;
+    Ok(path)
+}
+
+// Saves the last rendered frame to the given PNG file. If a size is given,
+// fails if the frame has a different size: the window manager and the display
+// scale can change the size of the window.
+pub fn write_png(window: &Window, path: &Path, size: Option<(u32, u32)>) -> Result<(), String> {
+    let image = window.snap_image();
+    if let Some((width, height)) = size {
+        let (actual_width, actual_height) = image.dimensions();
+        if (actual_width, actual_height) != (width, height) {
+            return Err(format!(
+                "the window is {}x{} pixels instead of {}x{} (is the display scaled?)",
+                actual_width, actual_height, width, height
+            ));
+        }
+    }
+    image
+        .save(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// Returns a path that doesn't exist yet, e.g. "2017-08-21_182500.png" or
+// "2017-08-21_182500-2.png" if that one already exists.
+fn unique_path(dir: &Path, timestamp: DateTime<Utc>) -> PathBuf {
+    let name = timestamp.format("%Y-%m-%d_%H%M%S").to_string();
+    let mut path = dir.join(format!("{}.png", name));
+    let mut i = 1;
+    while path.exists() {
+        i += 1;
+        path = dir.join(format!("{}-{}.png", name, i));
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::parse_utc;
+
+    #[test]
+    fn unique() {
+        let dir = std::env::temp_dir().join(format!("planets-rs-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let t = parse_utc("2017-08-21 18:25:07").unwrap();
+        let first = unique_path(&dir, t);
+        assert_eq!(first, dir.join("2017-08-21_182507.png"));
+        fs::write(&first, "").unwrap();
+        assert_eq!(unique_path(&dir, t), dir.join("2017-08-21_182507-2.png"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
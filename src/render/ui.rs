@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+
 use conrod::position::{Align, Direction, Padding, Position, Relative};
 use conrod::widget::Id;
 use kiss3d::conrod::position::Place;
@@ -10,10 +12,20 @@ use kiss3d::{
 
 use crate::choice::Choice;
 use crate::control::{self, ControlEvent};
+use crate::eclipse::{self, EclipseKind};
+use crate::lunar::{MoonPhase, OrbitEventKind};
+use crate::simulation::{SearchKind, Seconds, Simulation};
 use crate::state::{RenderState, SimulationState};
+use crate::time::parse_utc;
 
 pub struct Ui {
     pub show_help: bool,
+    // Contents of the "Go to" text box.
+    go_to_text: RefCell<String>,
+    // Contents of the speed text box while it is being edited.
+    speed_text: RefCell<Option<String>>,
+    // Ids of the eclipse markers on the timeline, one per marker.
+    timeline_markers: RefCell<widget::id::List>,
     ids: Ids,
 }
 
@@ -31,6 +43,9 @@ impl Ui {
 
         Self {
             show_help: false,
+            go_to_text: RefCell::new(String::new()),
+            speed_text: RefCell::new(None),
+            timeline_markers: RefCell::new(widget::id::List::new()),
             ids: Ids::new(conrod_ui.widget_id_generator()),
         }
     }
@@ -80,11 +95,19 @@ impl Ui {
             .center_justify()
             .set(self.ids.timestamp, ui);
 
+        self.moon_phase(ui, sim_state);
+        self.timeline(ui, sim_state, &mut events);
+        self.go_to(ui, sim_state, &mut events);
         self.simulation_controls(ui, sim_state, &mut events);
         self.simulation_speed(ui, sim_state, &mut events);
         self.simulation_presets(ui, sim_state, &mut events);
         self.camera_focus(ui, render_state, &mut events);
+        self.integrator(ui, sim_state, &mut events);
         self.render_toggles(ui, render_state, &mut events);
+        self.eclipses(ui, sim_state, &mut events);
+        self.phases(ui, sim_state, &mut events);
+        self.orbit_events(ui, sim_state, &mut events);
+        self.diagnostics(ui, sim_state);
 
         widget::Text::new("Press H for help")
             .font_size(12)
@@ -102,6 +125,135 @@ impl Ui {
         events
     }
 
+    fn moon_phase(&self, ui: &mut UiCell, sim_state: &dyn SimulationState) {
+        let phase = MoonPhase::new(&sim_state.snapshot());
+        let text = format!(
+            "Moon: {}, {:.1}% lit\nPhase angle {:.1}°, elongation {:.1}°",
+            phase.name.name(),
+            phase.illuminated * 100.0,
+            phase.phase_angle,
+            phase.elongation
+        );
+        widget::Text::new(&text)
+            .font_size(11)
+            .padded_w_of(self.ids.canvas, Self::MARGIN)
+            .down_from(self.ids.timestamp, 6.0)
+            .align_middle_x_of(self.ids.canvas)
+            .center_justify()
+            .set(self.ids.moon_phase, ui);
+    }
+
+    // A slider over the time span of the preset, with markers for the eclipses
+    // in it. Dragging it moves the simulation.
+    fn timeline(
+        &self,
+        ui: &mut UiCell,
+        sim_state: &dyn SimulationState,
+        events: &mut Vec<ControlEvent>,
+    ) {
+        let preset = sim_state.preset();
+        let start = preset.snapshot.timestamp;
+        let span = Seconds::from(preset.span).0;
+        let offset = |t: chrono::DateTime<chrono::Utc>| Seconds::from(t - start).0;
+        let width = Self::WIDTH - 2.0 * Self::MARGIN;
+
+        let position = offset(sim_state.timestamp()).clamp(0.0, span);
+        if let Some(value) = widget::Slider::new(position, 0.0, span)
+            .w_h(width, 14.0)
+            .align_middle_x_of(self.ids.canvas)
+            .down_from(self.ids.moon_phase, 10.0)
+            .color(color::LIGHT_CHARCOAL)
+            .set(self.ids.timeline, ui)
+        {
+            events.push(ControlEvent::SeekTo(start + Seconds(value).to_duration()));
+        }
+
+        let markers = sim_state.timeline_eclipses();
+        let mut ids = self.timeline_markers.borrow_mut();
+        ids.resize(markers.len(), &mut ui.widget_id_generator());
+        for (e, &id) in markers.iter().zip(ids.iter()) {
+            widget::Rectangle::fill([2.0, 14.0])
+                .color(match e.kind {
+                    EclipseKind::Solar => color::YELLOW,
+                    EclipseKind::Lunar => color::RED,
+                })
+                .x_relative_to(self.ids.timeline, (offset(e.greatest) / span - 0.5) * width)
+                .align_middle_y_of(self.ids.timeline)
+                .graphics_for(self.ids.timeline)
+                .set(id, ui);
+        }
+
+        widget::Text::new(&start.format("%Y-%m-%d").to_string())
+            .font_size(10)
+            .align_left_of(self.ids.timeline)
+            .down_from(self.ids.timeline, 4.0)
+            .set(self.ids.timeline_start, ui);
+        widget::Text::new(&(start + preset.span).format("%Y-%m-%d").to_string())
+            .font_size(10)
+            .align_right_of(self.ids.timeline)
+            .down_from(self.ids.timeline, 4.0)
+            .set(self.ids.timeline_end, ui);
+    }
+
+    // A text box to jump to a given UTC time. The button shows the progress
+    // of long jumps.
+    fn go_to(
+        &self,
+        ui: &mut UiCell,
+        sim_state: &dyn SimulationState,
+        events: &mut Vec<ControlEvent>,
+    ) {
+        widget::Text::new("Go to:")
+            .font_size(12)
+            .x_place_on(self.ids.canvas, Place::Start(Some(2.0)))
+            .down_from(self.ids.timeline_start, 20.0)
+            .set(self.ids.go_to_title, ui);
+
+        let mut text = self.go_to_text.borrow_mut();
+        let is_valid = text.is_empty() || parse_utc(&text).is_ok();
+        let mut submit = false;
+        for event in widget::TextBox::new(&text)
+            .font_size(11)
+            .text_color(if is_valid {
+                ui.theme.label_color
+            } else {
+                color::LIGHT_RED
+            })
+            .color(color::DARK_CHARCOAL)
+            .right(10.0)
+            .align_middle_y()
+            .w_h(150.0, 25.0)
+            .set(self.ids.go_to_text, ui)
+        {
+            match event {
+                widget::text_box::Event::Update(s) => *text = s,
+                widget::text_box::Event::Enter => submit = true,
+            }
+        }
+
+        let label = match sim_state.seek_progress() {
+            Some(progress) => format!("{:.0}%", progress * 100.0),
+            None => "Go".to_string(),
+        };
+        for _ in widget::Button::new()
+            .label(&label)
+            .label_font_size(11)
+            .label_y(Relative::Scalar(1.0))
+            .color(color::DARK_CHARCOAL)
+            .right(6.0)
+            .align_middle_y()
+            .w_h(50.0, 25.0)
+            .set(self.ids.go_to_button, ui)
+        {
+            submit = true;
+        }
+        if submit {
+            if let Ok(t) = parse_utc(&text) {
+                events.push(ControlEvent::SeekTo(t));
+            }
+        }
+    }
+
     fn simulation_controls(
         &self,
         ui: &mut UiCell,
@@ -113,7 +265,7 @@ impl Ui {
             for _ in widget::Button::new()
                 .color(ui.theme().label_color.with_luminance(0.1))
                 .align_middle_x_of(self.ids.canvas)
-                .down(20.0)
+                .down_from(self.ids.go_to_text, 16.0)
                 .w_h(40.0, 40.0)
                 .set(self.ids.play_pause, ui)
             {
@@ -172,7 +324,7 @@ impl Ui {
             for _ in widget::Button::new()
                 .color(ui.theme().label_color.with_luminance(0.1))
                 .align_middle_x_of(self.ids.canvas)
-                .down(20.0)
+                .down_from(self.ids.go_to_text, 16.0)
                 .w_h(40.0, 40.0)
                 .set(self.ids.pause, ui)
             {
@@ -211,16 +363,64 @@ impl Ui {
         sim_state: &dyn SimulationState,
         events: &mut Vec<ControlEvent>,
     ) {
-        if let Some(new_speed) = self.choice_buttons(
-            ui,
-            self.ids.speed_title,
-            "Simulation speed (time/wall-sec)",
-            self.ids.play_pause,
-            26.0,
-            &sim_state.speed(),
-            |&d| duration_short_string(&d),
-        ) {
-            events.push(ControlEvent::SetSpeed(new_speed))
+        let title = if sim_state.is_fixed_step() {
+            "Simulation speed (time/60 frames)"
+        } else {
+            "Simulation speed (time/wall-sec)"
+        };
+        widget::Text::new(title)
+            .font_size(12)
+            .align_middle_x_of(self.ids.canvas)
+            .down_from(self.ids.play_pause, 30.0)
+            .center_justify()
+            .set(self.ids.speed_title, ui);
+
+        // The slider has a logarithmic scale.
+        let speed = sim_state.speed();
+        if let Some(value) = widget::Slider::new(
+            Seconds::from(speed).0.ln(),
+            Simulation::MIN_SPEED.0.ln(),
+            Simulation::MAX_SPEED.0.ln(),
+        )
+        .w_h(170.0, 20.0)
+        .x_place_on(self.ids.canvas, Place::Start(Some(2.0)))
+        .down_from(self.ids.speed_title, 8.0)
+        .color(color::LIGHT_CHARCOAL)
+        .set(self.ids.speed_slider, ui)
+        {
+            events.push(ControlEvent::SetSpeed(Seconds(value.exp()).to_duration()));
+        }
+
+        // Shows the current speed, unless the user is typing one.
+        let mut text = self.speed_text.borrow_mut();
+        if ui.global_input().current.widget_capturing_keyboard != Some(self.ids.speed_text) {
+            *text = None;
+        }
+        let shown = text
+            .clone()
+            .unwrap_or_else(|| format!("{}/s", duration_short_string(&speed)));
+        for event in widget::TextBox::new(&shown)
+            .font_size(11)
+            .text_color(if parse_speed(&shown).is_some() {
+                ui.theme.label_color
+            } else {
+                color::LIGHT_RED
+            })
+            .color(color::DARK_CHARCOAL)
+            .right(6.0)
+            .align_middle_y()
+            .w_h(Self::WIDTH - 2.0 * Self::MARGIN - 176.0, 20.0)
+            .set(self.ids.speed_text, ui)
+        {
+            match event {
+                widget::text_box::Event::Update(s) => *text = Some(s),
+                widget::text_box::Event::Enter => {
+                    if let Some(speed) = parse_speed(&shown) {
+                        events.push(ControlEvent::SetSpeed(speed));
+                        *text = None;
+                    }
+                }
+            }
         }
     }
 
@@ -234,7 +434,7 @@ impl Ui {
             .font_size(12)
             .align_left_of(self.ids.canvas)
             .x_place_on(self.ids.canvas, Place::Start(Some(2.0)))
-            .down_from(self.ids.speed_1, 30.0)
+            .down_from(self.ids.speed_slider, 30.0)
             .set(self.ids.preset_title, ui);
         let current_preset = sim_state.preset();
         let presets: Vec<String> = current_preset
@@ -279,6 +479,25 @@ impl Ui {
         }
     }
 
+    fn integrator(
+        &self,
+        ui: &mut UiCell,
+        sim_state: &dyn SimulationState,
+        events: &mut Vec<ControlEvent>,
+    ) {
+        if let Some(new_integrator) = self.choice_buttons(
+            ui,
+            self.ids.integrator_title,
+            "Integrator",
+            self.ids.camera_1,
+            26.0,
+            &sim_state.integrator(),
+            |k| k.name().to_string(),
+        ) {
+            events.push(ControlEvent::SetIntegrator(new_integrator))
+        }
+    }
+
     fn render_toggles(
         &self,
         ui: &mut UiCell,
@@ -318,6 +537,28 @@ impl Ui {
             events.push(ControlEvent::ToggleEarthAxis)
         }
 
+        if self.toggle_switch(
+            ui,
+            self.ids.eclipse_path_toggle_title,
+            "Eclipse path",
+            ui.maybe_prev_widget().unwrap(),
+            Relative::Direction(Direction::Backwards, 20.0),
+            render_state.show_eclipse_path(),
+        ) {
+            events.push(ControlEvent::ToggleEclipsePath)
+        }
+
+        if self.toggle_switch(
+            ui,
+            self.ids.orbit_markers_toggle_title,
+            "Lunar apsides/nodes",
+            ui.maybe_prev_widget().unwrap(),
+            Relative::Direction(Direction::Backwards, 20.0),
+            render_state.show_orbit_markers(),
+        ) {
+            events.push(ControlEvent::ToggleOrbitMarkers)
+        }
+
         if self.toggle_switch(
             ui,
             self.ids.skybox_toggle_title,
@@ -330,6 +571,229 @@ impl Ui {
         }
     }
 
+    fn eclipses(
+        &self,
+        ui: &mut UiCell,
+        sim_state: &dyn SimulationState,
+        events: &mut Vec<ControlEvent>,
+    ) {
+        widget::Text::new("Eclipses:")
+            .font_size(12)
+            .x_place_on(self.ids.canvas, Place::Start(Some(2.0)))
+            .down_from(self.ids.skybox_toggle_title, 30.0)
+            .set(self.ids.eclipse_title, ui);
+
+        for _ in widget::Button::new()
+            .label(&find_label(sim_state.search_progress(SearchKind::Eclipses)))
+            .label_font_size(11)
+            .label_y(Relative::Scalar(1.0))
+            .color(color::DARK_CHARCOAL)
+            .right(10.0)
+            .align_middle_y()
+            .w_h(50.0, 25.0)
+            .set(self.ids.eclipse_find, ui)
+        {
+            events.push(ControlEvent::FindEclipses);
+        }
+
+        let eclipses = sim_state.eclipses();
+        let items: Vec<String> = eclipses
+            .iter()
+            .map(|e| {
+                format!(
+                    "{} {} ({:.3})",
+                    e.greatest.format("%Y-%m-%d %H:%M"),
+                    e.description(),
+                    e.magnitude
+                )
+            })
+            .collect();
+        if let Some(selected_idx) = widget::DropDownList::new(&items, None)
+            .label(if items.is_empty() {
+                "(none found)"
+            } else {
+                "Jump to greatest eclipse"
+            })
+            .label_font_size(11)
+            .label_y(Relative::Scalar(1.0))
+            .right(6.0)
+            .align_middle_y()
+            .w_h(150.0, 25.0)
+            .color(color::DARK_CHARCOAL)
+            .set(self.ids.eclipse_list, ui)
+        {
+            events.push(ControlEvent::SeekTo(eclipses[selected_idx].greatest));
+        }
+
+        widget::Text::new("Contacts:")
+            .font_size(12)
+            .x_place_on(self.ids.canvas, Place::Start(Some(2.0)))
+            .down_from(self.ids.eclipse_title, 20.0)
+            .set(self.ids.contact_title, ui);
+
+        let (contacts, items): (&[eclipse::ContactTime], Vec<String>) =
+            match eclipse::nearest(eclipses, sim_state.timestamp()) {
+                Some(e) => (
+                    &e.contacts,
+                    e.contacts
+                        .iter()
+                        .map(|c| {
+                            format!(
+                                "{} {} {}",
+                                c.timestamp.format("%H:%M:%S"),
+                                c.contact.name(),
+                                c.contact.description(e.kind)
+                            )
+                        })
+                        .collect(),
+                ),
+                None => (&[], Vec::new()),
+            };
+        if let Some(selected_idx) = widget::DropDownList::new(&items, None)
+            .label(if items.is_empty() {
+                "(no eclipse nearby)"
+            } else {
+                "Jump to contact"
+            })
+            .label_font_size(11)
+            .label_y(Relative::Scalar(1.0))
+            .x_place_on(self.ids.canvas, Place::End(Some(2.0)))
+            .align_middle_y_of(self.ids.contact_title)
+            .w_h(150.0, 25.0)
+            .color(color::DARK_CHARCOAL)
+            .set(self.ids.contact_list, ui)
+        {
+            events.push(ControlEvent::SeekTo(contacts[selected_idx].timestamp));
+        }
+    }
+
+    fn phases(
+        &self,
+        ui: &mut UiCell,
+        sim_state: &dyn SimulationState,
+        events: &mut Vec<ControlEvent>,
+    ) {
+        widget::Text::new("Phases:")
+            .font_size(12)
+            .x_place_on(self.ids.canvas, Place::Start(Some(2.0)))
+            .down_from(self.ids.contact_title, 20.0)
+            .set(self.ids.phase_title, ui);
+
+        for _ in widget::Button::new()
+            .label(&find_label(sim_state.search_progress(SearchKind::Phases)))
+            .label_font_size(11)
+            .label_y(Relative::Scalar(1.0))
+            .color(color::DARK_CHARCOAL)
+            .align_left_of(self.ids.eclipse_find)
+            .align_middle_y_of(self.ids.phase_title)
+            .w_h(50.0, 25.0)
+            .set(self.ids.phase_find, ui)
+        {
+            events.push(ControlEvent::FindPhases);
+        }
+
+        let phases = sim_state.phases();
+        let items: Vec<String> = phases
+            .iter()
+            .map(|p| format!("{} {}", p.timestamp.format("%Y-%m-%d %H:%M"), p.name.name()))
+            .collect();
+        if let Some(selected_idx) = widget::DropDownList::new(&items, None)
+            .label(if items.is_empty() {
+                "(none found)"
+            } else {
+                "Jump to phase"
+            })
+            .label_font_size(11)
+            .label_y(Relative::Scalar(1.0))
+            .align_left_of(self.ids.eclipse_list)
+            .align_middle_y_of(self.ids.phase_title)
+            .w_h(150.0, 25.0)
+            .color(color::DARK_CHARCOAL)
+            .set(self.ids.phase_list, ui)
+        {
+            events.push(ControlEvent::SeekTo(phases[selected_idx].timestamp));
+        }
+    }
+
+    fn orbit_events(
+        &self,
+        ui: &mut UiCell,
+        sim_state: &dyn SimulationState,
+        events: &mut Vec<ControlEvent>,
+    ) {
+        widget::Text::new("Orbit:")
+            .font_size(12)
+            .x_place_on(self.ids.canvas, Place::Start(Some(2.0)))
+            .down_from(self.ids.phase_title, 20.0)
+            .set(self.ids.orbit_title, ui);
+
+        for _ in widget::Button::new()
+            .label(&find_label(
+                sim_state.search_progress(SearchKind::OrbitEvents),
+            ))
+            .label_font_size(11)
+            .label_y(Relative::Scalar(1.0))
+            .color(color::DARK_CHARCOAL)
+            .align_left_of(self.ids.eclipse_find)
+            .align_middle_y_of(self.ids.orbit_title)
+            .w_h(50.0, 25.0)
+            .set(self.ids.orbit_find, ui)
+        {
+            events.push(ControlEvent::FindOrbitEvents);
+        }
+
+        let orbit_events = sim_state.orbit_events();
+        let items: Vec<String> = orbit_events
+            .iter()
+            .map(|e| {
+                let distance = match e.kind {
+                    OrbitEventKind::Perigee | OrbitEventKind::Apogee => {
+                        format!(" {:.0} km", e.distance)
+                    }
+                    _ => String::new(),
+                };
+                format!(
+                    "{} {}{}",
+                    e.timestamp.format("%Y-%m-%d %H:%M"),
+                    e.kind.name(),
+                    distance
+                )
+            })
+            .collect();
+        if let Some(selected_idx) = widget::DropDownList::new(&items, None)
+            .label(if items.is_empty() {
+                "(none found)"
+            } else {
+                "Jump to apsis/node"
+            })
+            .label_font_size(11)
+            .label_y(Relative::Scalar(1.0))
+            .align_left_of(self.ids.eclipse_list)
+            .align_middle_y_of(self.ids.orbit_title)
+            .w_h(150.0, 25.0)
+            .color(color::DARK_CHARCOAL)
+            .set(self.ids.orbit_list, ui)
+        {
+            events.push(ControlEvent::SeekTo(orbit_events[selected_idx].timestamp));
+        }
+    }
+
+    fn diagnostics(&self, ui: &mut UiCell, sim_state: &dyn SimulationState) {
+        let drift = sim_state.drift();
+        let lines = [
+            "Drift since preset was loaded:".to_string(),
+            format!("  energy            {:.2e}", drift.energy),
+            format!("  momentum          {:.2e}", drift.momentum),
+            format!("  angular momentum  {:.2e}", drift.angular_momentum),
+        ];
+        widget::Text::new(&lines.join("\n"))
+            .font_size(11)
+            .line_spacing(2.0)
+            .x_place_on(self.ids.canvas, Place::Start(Some(2.0)))
+            .down_from(self.ids.orbit_title, 24.0)
+            .set(self.ids.diagnostics, ui);
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn choice_buttons<T: Copy>(
         &self,
@@ -443,7 +907,7 @@ impl Ui {
             .bottom_left_of(ui.window)
             // .x_relative_to(ui.window, -Self::WIDTH * 0.5)
             // .align_middle_y_of(ui.window)
-            .w_h(350.0, 300.0)
+            .w_h(350.0, 350.0)
             .set(self.ids.help_canvas, ui);
 
         widget::Text::new(control::help_text().trim_end())
@@ -483,6 +947,13 @@ widget_ids! {
         canvas_scrollbar,
         footer,
         timestamp,
+        moon_phase,
+        timeline,
+        timeline_start,
+        timeline_end,
+        go_to_title,
+        go_to_text,
+        go_to_button,
         play_pause,
         jump_back,
         jump_back_shape_1,
@@ -498,15 +969,8 @@ widget_ids! {
         pause_shape_2,
         reverse,
         speed_title,
-        speed_1,
-        speed_2,
-        speed_3,
-        speed_4,
-        speed_5,
-        speed_6,
-        speed_7,
-        speed_8,
-        speed_9,
+        speed_slider,
+        speed_text,
         preset_title,
         preset_list,
         camera_title,
@@ -516,6 +980,11 @@ widget_ids! {
         camera_4,
         camera_5,
         camera_6,
+        integrator_title,
+        integrator_1,
+        integrator_2,
+        integrator_3,
+        integrator_4,
         reverse_toggle_title,
         reverse_toggle_rect,
         reverse_toggle_circle,
@@ -528,9 +997,27 @@ widget_ids! {
         earth_axis_toggle_title,
         earth_axis_toggle_rect,
         earth_axis_toggle_circle,
+        eclipse_path_toggle_title,
+        eclipse_path_toggle_rect,
+        eclipse_path_toggle_circle,
+        orbit_markers_toggle_title,
+        orbit_markers_toggle_rect,
+        orbit_markers_toggle_circle,
         skybox_toggle_title,
         skybox_toggle_rect,
         skybox_toggle_circle,
+        eclipse_title,
+        eclipse_find,
+        eclipse_list,
+        contact_title,
+        contact_list,
+        phase_title,
+        phase_find,
+        phase_list,
+        orbit_title,
+        orbit_find,
+        orbit_list,
+        diagnostics,
         footer_msg,
         fps,
         help_canvas,
@@ -538,16 +1025,50 @@ widget_ids! {
     }
 }
 
+// Label of a Find button: the progress of the search, if any.
+fn find_label(progress: Option<f64>) -> String {
+    match progress {
+        Some(progress) => format!("{:.0}%", progress * 100.0),
+        None => "Find".to_string(),
+    }
+}
+
+// Formats a duration with the largest unit that fits, e.g. "15m" or "1.5d".
 fn duration_short_string(d: &chrono::Duration) -> String {
-    if d.num_days() > 0 {
-        format!("{}d", d.num_days())
-    } else if d.num_hours() > 0 {
-        format!("{}h", d.num_hours())
-    } else if d.num_minutes() > 0 {
-        format!("{}m", d.num_minutes())
+    let seconds = Seconds::from(*d).0;
+    let (value, unit) = [(86400.0, "d"), (3600.0, "h"), (60.0, "m")]
+        .into_iter()
+        .find(|(unit, _)| seconds >= *unit)
+        .map_or((seconds, "s"), |(unit, name)| (seconds / unit, name));
+    // Only show the first decimal of small values.
+    let value = if value < 10.0 {
+        format!("{:.1}", value)
     } else {
-        format!("{}s", d.num_seconds())
+        format!("{:.0}", value)
+    };
+    format!("{}{}", value.trim_end_matches(".0"), unit)
+}
+
+// Parses a speed given as a duration per second, e.g. "3 h/s", "1.5d" or
+// "1 s/s" (real time). The units are s, m (or min), h, d, w and y.
+fn parse_speed(s: &str) -> Option<chrono::Duration> {
+    let s = s.trim();
+    let s = s.strip_suffix("/s").unwrap_or(s);
+    let split = s.find(|c: char| c.is_ascii_alphabetic())?;
+    let value: f64 = s[..split].trim().parse().ok()?;
+    let unit = match s[split..].trim() {
+        "s" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 3600.0,
+        "d" => 86400.0,
+        "w" => 7.0 * 86400.0,
+        "y" => 365.25 * 86400.0,
+        _ => return None,
+    };
+    if value <= 0.0 {
+        return None;
     }
+    Some(Seconds(value * unit).to_duration())
 }
 
 #[cfg(test)]
@@ -572,5 +1093,31 @@ mod tests {
         for (d, expected) in tests {
             assert_eq!(duration_short_string(&d), expected);
         }
+        assert_eq!(duration_short_string(&Duration::minutes(90)), "1.5h");
+        assert_eq!(duration_short_string(&Duration::minutes(599)), "10h");
+        assert_eq!(duration_short_string(&Duration::hours(12)), "12h");
+    }
+
+    #[test]
+    fn speed() {
+        use chrono::Duration;
+        for (s, expected) in [
+            ("3 h/s", Duration::hours(3)),
+            ("1 s/s", Duration::seconds(1)),
+            ("1.5d", Duration::hours(36)),
+            (" 15 min ", Duration::minutes(15)),
+            ("2w/s", Duration::days(14)),
+        ] {
+            assert_eq!(parse_speed(s), Some(expected), "{}", s);
+        }
+        for s in ["", "3", "h/s", "3 x/s", "0 s/s", "-1 h"] {
+            assert_eq!(parse_speed(s), None, "{}", s);
+        }
+        // The speed shown in the UI can be parsed back.
+        let d = Duration::days(90);
+        assert_eq!(
+            parse_speed(&format!("{}/s", duration_short_string(&d))),
+            Some(d)
+        );
     }
 }
@@ -1,49 +1,121 @@
 use std::{
     ops::{Deref, DerefMut},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use crate::{config::Preset, state::SimulationState};
-
-use self::seconds::Seconds;
+use crate::{
+    config::{Preset, Session},
+    eclipse::{self, Eclipse},
+    lunar::{self, OrbitEvent, PhaseEvent},
+    state::SimulationState,
+    time,
+};
 
-use super::{body::BodyProperties, choice::Choice, control::ControlEvent};
+use super::{choice::Choice, control::ControlEvent};
 use chrono::{DateTime, Utc};
-use kiss3d::nalgebra::{Point3, Vector3};
 
+mod checkpoints;
+mod clock;
+mod diagnostics;
+mod gravity;
+mod integrator;
+mod search;
 mod seconds;
 mod snapshot;
 
-pub use snapshot::Snapshot;
+use checkpoints::Checkpoints;
+use clock::{Pacing, WallClock};
+pub use diagnostics::{Drift, Invariants};
+pub use integrator::IntegratorKind;
+use search::Search;
+pub use search::SearchKind;
+pub use seconds::Seconds;
+pub use snapshot::{BodyState, Snapshot};
 
 pub struct Simulation {
     preset: Choice<Preset>,
     current: Snapshot,
     // Simulated duration per elapsed second.
-    speed: Choice<chrono::Duration>,
+    speed: chrono::Duration,
     reverse: bool,
+    integrator: Choice<IntegratorKind>,
+    // Invariants at the time the preset was loaded; used to calculate drift.
+    initial_invariants: Invariants,
+    // Results of the last eclipse search.
+    eclipses: Vec<Eclipse>,
+    // Results of the last lunar phase search.
+    phases: Vec<PhaseEvent>,
+    // Results of the last search for the apsides and nodes of the Moon.
+    orbit_events: Vec<OrbitEvent>,
+    // Searches in progress, continued in advance. Their results are added to
+    // the lists above as they are found.
+    eclipse_search: Option<Search<Eclipse>>,
+    phase_search: Option<Search<PhaseEvent>>,
+    orbit_search: Option<Search<OrbitEvent>>,
+    // Eclipses in the time span of the preset, for the timeline. They are
+    // searched when the preset is loaded.
+    timeline_eclipses: Vec<Eclipse>,
+    timeline_search: Option<Search<Eclipse>>,
+    // Recent states, used to go back in time without integrating backwards.
+    checkpoints: Checkpoints,
+    pacing: Pacing,
     state: State,
 }
 
 struct StartInfo {
-    instant: Instant,
+    // Reading of the clock at start().
+    clock: Duration,
+    // Frames (calls to advance) since start().
+    frames: u32,
     timestamp: DateTime<Utc>,
 }
 
+struct SeekInfo {
+    target: DateTime<Utc>,
+    // Time when the seek started, to show the progress.
+    from: DateTime<Utc>,
+    // Whether to start running when the target is reached.
+    resume: bool,
+}
+
 enum State {
     Stopped,
     Running(StartInfo),
+    // Moving to a given time as fast as possible; see seek_to.
+    Seeking(SeekInfo),
 }
 
 impl Simulation {
-    pub fn new(preset: &Choice<Preset>, speed: &Choice<chrono::Duration>) -> Self {
-        Simulation {
+    pub fn new(
+        preset: &Choice<Preset>,
+        speed: chrono::Duration,
+        integrator: &Choice<IntegratorKind>,
+    ) -> Self {
+        let mut integrator = integrator.clone();
+        if let Some(kind) = preset.integrator {
+            integrator = integrator.choice_set().by_value(kind);
+        }
+        let mut sim = Simulation {
             preset: preset.clone(),
             current: preset.snapshot,
-            speed: speed.clone(),
+            speed,
             state: State::Stopped,
             reverse: false,
-        }
+            integrator,
+            initial_invariants: Invariants::new(&preset.snapshot),
+            eclipses: Vec::new(),
+            phases: Vec::new(),
+            orbit_events: Vec::new(),
+            eclipse_search: None,
+            phase_search: None,
+            orbit_search: None,
+            timeline_eclipses: Vec::new(),
+            timeline_search: None,
+            checkpoints: Checkpoints::new(&preset.snapshot),
+            pacing: Pacing::Clock(Box::new(WallClock::new())),
+        };
+        sim.find_timeline_eclipses();
+        sim
     }
 
     pub fn current(&self) -> Snapshot {
@@ -51,8 +123,13 @@ impl Simulation {
     }
 
     pub fn start(&mut self) {
+        let clock = match &self.pacing {
+            Pacing::Clock(clock) => clock.now(),
+            Pacing::FixedStep(_) => Duration::ZERO,
+        };
         self.state = State::Running(StartInfo {
-            instant: Instant::now(),
+            clock,
+            frames: 0,
             timestamp: self.current.timestamp,
         });
     }
@@ -61,10 +138,17 @@ impl Simulation {
         self.state = State::Stopped
     }
 
+    // Sets how the simulated time follows the frames (by default, the wall
+    // clock).
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.stopped().pacing = pacing;
+    }
+
     pub fn toggle_start(&mut self) {
         match &self.state {
             State::Running(_) => self.stop(),
-            State::Stopped => self.start(),
+            // Starting cancels the seek.
+            State::Stopped | State::Seeking(_) => self.start(),
         }
     }
 
@@ -76,22 +160,31 @@ impl Simulation {
     const MAX_STEPS_PER_FRAME: u32 = 1000;
 
     pub fn advance(&mut self) {
-        if let State::Running(start_info) = &self.state {
-            let simulation_speed_per_sec = Seconds::from(self.speed.get());
+        self.continue_searches(Instant::now() + Self::SEARCH_TIME_PER_FRAME);
+        if let State::Seeking(_) = &self.state {
+            self.continue_seek();
+            return;
+        }
+        if let State::Running(start_info) = &mut self.state {
+            let simulation_speed_per_sec = Seconds::from(self.speed);
 
-            // Wall time elapsed since start().
-            let elapsed = Seconds::from(start_info.instant.elapsed());
-            assert!(elapsed.0 >= 0.0);
+            // Wall time elapsed since start() (or as if it elapsed, for fixed
+            // steps).
+            start_info.frames += 1;
+            let elapsed = Seconds::from(match &self.pacing {
+                Pacing::Clock(clock) => clock.now().saturating_sub(start_info.clock),
+                Pacing::FixedStep(step) => *step * start_info.frames,
+            });
 
             // Simulation time elapsed since start().
             let simulation_elapsed = simulation_speed_per_sec * elapsed.0;
 
             let simulation_advance = if !self.reverse {
                 let target_timestamp = start_info.timestamp + simulation_elapsed.to_duration();
-                Seconds::from(target_timestamp - self.current.timestamp)
+                Seconds::between(&self.current.timestamp, &target_timestamp)
             } else {
                 let target_timestamp = start_info.timestamp - simulation_elapsed.to_duration();
-                Seconds::from(self.current.timestamp - target_timestamp)
+                Seconds::between(&target_timestamp, &self.current.timestamp)
             };
 
             let target_step = Simulation::DEFAULT_STEP
@@ -105,6 +198,19 @@ impl Simulation {
 
     fn advance_by(&mut self, simulation_advance: Seconds, num_steps: u32) {
         assert!(simulation_advance.0 >= 0.0);
+        if self.reverse && simulation_advance.0 > 0.0 {
+            let target = time::add_elapsed(&self.current.timestamp, -simulation_advance.0);
+            if let Some(checkpoint) = self.checkpoints.latest_before(target) {
+                self.current = propagate(
+                    checkpoint,
+                    self.integrator.get(),
+                    target,
+                    Self::DEFAULT_STEP,
+                );
+                return;
+            }
+            // Past the oldest checkpoint: integrate backwards.
+        }
         let num_steps = num_steps.min(Simulation::MAX_STEPS_PER_FRAME);
         let step = simulation_advance / num_steps as f64 * if self.reverse { -1.0 } else { 1.0 };
         for _ in 0..num_steps {
@@ -113,36 +219,189 @@ impl Simulation {
     }
 
     fn step(&mut self, dt: Seconds) {
-        let s = &self.current;
-        let new_timestamp = s.timestamp + dt.to_duration();
-        let dt = dt.0;
+        self.integrator
+            .integrator()
+            .step(&mut self.current.bodies, dt.0);
+        self.current.timestamp = time::add_elapsed(&self.current.timestamp, dt.0);
+        if dt.0 > 0.0 {
+            self.checkpoints.record(&self.current);
+        }
+    }
+
+    // Moves the simulation to the given time, independently of the wall clock
+    // (e.g. to render frames at fixed times). Integrates with equal steps of
+    // at most max_step, so the result is the same on every run.
+    pub fn advance_to(&mut self, timestamp: DateTime<Utc>, max_step: Seconds) {
+        self.stop();
+        self.current = propagate(&self.current, self.integrator.get(), timestamp, max_step);
+        self.checkpoints.record(&self.current);
+    }
+
+    // How far ahead to search for eclipses.
+    const ECLIPSE_SEARCH_DAYS: i64 = 365;
+
+    // Starts searching for eclipses. The search continues over several frames
+    // (see search_progress).
+    pub fn find_eclipses(&mut self) {
+        let end = self.current.timestamp + chrono::Duration::days(Self::ECLIPSE_SEARCH_DAYS);
+        self.eclipses.clear();
+        self.eclipse_search = Some(Search::new(
+            eclipse::find_eclipses,
+            |e: &Eclipse| e.greatest,
+            &self.current,
+            self.integrator.get(),
+            end,
+            Self::DEFAULT_STEP,
+        ));
+    }
+
+    // How far ahead to search for lunar phases and orbit events.
+    const PHASE_SEARCH_DAYS: i64 = 365;
+
+    pub fn find_phases(&mut self) {
+        let end = self.current.timestamp + chrono::Duration::days(Self::PHASE_SEARCH_DAYS);
+        self.phases.clear();
+        self.phase_search = Some(Search::new(
+            lunar::find_phases,
+            |p: &PhaseEvent| p.timestamp,
+            &self.current,
+            self.integrator.get(),
+            end,
+            Self::DEFAULT_STEP,
+        ));
+    }
+
+    pub fn find_orbit_events(&mut self) {
+        let end = self.current.timestamp + chrono::Duration::days(Self::PHASE_SEARCH_DAYS);
+        self.orbit_events.clear();
+        self.orbit_search = Some(Search::new(
+            lunar::find_orbit_events,
+            |e: &OrbitEvent| e.timestamp,
+            &self.current,
+            self.integrator.get(),
+            end,
+            Self::DEFAULT_STEP,
+        ));
+    }
+
+    // Starts searching for the eclipses in the time span of the preset.
+    fn find_timeline_eclipses(&mut self) {
+        let start = &self.preset.snapshot;
+        self.timeline_eclipses.clear();
+        self.timeline_search = Some(Search::new(
+            eclipse::find_eclipses,
+            |e: &Eclipse| e.greatest,
+            start,
+            self.integrator.get(),
+            start.timestamp + self.preset.span,
+            Self::DEFAULT_STEP,
+        ));
+    }
 
-        // We use velocity Verlet integration:
-        //   x(t+dt) = x(t) + v(t)dt + a(t)dt^2/2
-        //   v(t+dt) = v(t) + ((a(t) + a(t+dt))dt/2
+    // Wall time spent searching in each frame, like SEEK_TIME_PER_FRAME.
+    const SEARCH_TIME_PER_FRAME: Duration = Duration::from_millis(30);
+
+    fn continue_searches(&mut self, deadline: Instant) {
+        continue_search(&mut self.eclipse_search, &mut self.eclipses, deadline);
+        continue_search(&mut self.phase_search, &mut self.phases, deadline);
+        continue_search(&mut self.orbit_search, &mut self.orbit_events, deadline);
+        continue_search(
+            &mut self.timeline_search,
+            &mut self.timeline_eclipses,
+            deadline,
+        );
+    }
 
-        let (earth_acc, moon_acc) = gacc_earth_and_moon(&s.earth_position, &s.moon_position);
+    // Completes the searches in progress, e.g. when the frames don't call
+    // advance.
+    pub fn finish_searches(&mut self) {
+        while self.eclipse_search.is_some()
+            || self.phase_search.is_some()
+            || self.orbit_search.is_some()
+            || self.timeline_search.is_some()
+        {
+            self.continue_searches(Instant::now() + Self::SEARCH_TIME_PER_FRAME);
+        }
+    }
 
-        let new_earth_pos = s.earth_position + s.earth_velocity * dt + 0.5 * earth_acc * dt * dt;
-        let new_moon_pos = s.moon_position + s.moon_velocity * dt + 0.5 * moon_acc * dt * dt;
+    fn cancel_searches(&mut self) {
+        self.eclipses.clear();
+        self.phases.clear();
+        self.orbit_events.clear();
+        self.eclipse_search = None;
+        self.phase_search = None;
+        self.orbit_search = None;
+    }
 
-        let (new_earth_acc, new_moon_acc) = gacc_earth_and_moon(&new_earth_pos, &new_moon_pos);
-        let new_earth_vel = s.earth_velocity + 0.5 * (earth_acc + new_earth_acc) * dt;
-        let new_moon_vel = s.moon_velocity + 0.5 * (moon_acc + new_moon_acc) * dt;
+    // Wall time spent seeking in each frame. This only limits the work done
+    // per frame; the result doesn't depend on it.
+    const SEEK_TIME_PER_FRAME: Duration = Duration::from_millis(30);
+
+    // Moves the simulation to the given time, as fast as possible. Long seeks
+    // continue over several frames (see seek_progress); changes to the
+    // simulation in the meantime cancel them.
+    pub fn seek_to(&mut self, timestamp: DateTime<Utc>) {
+        let resume = match &self.state {
+            State::Stopped => false,
+            State::Running(_) => true,
+            State::Seeking(info) => info.resume,
+        };
+        // Restart from the latest checkpoint before the target, unless the
+        // current state is closer.
+        if let Some(checkpoint) = self.checkpoints.latest_before(timestamp) {
+            if timestamp < self.current.timestamp || checkpoint.timestamp > self.current.timestamp {
+                self.current = *checkpoint;
+            }
+        }
+        self.state = State::Seeking(SeekInfo {
+            target: timestamp,
+            from: self.current.timestamp,
+            resume,
+        });
+        self.continue_seek();
+    }
 
-        self.current = Snapshot {
-            timestamp: new_timestamp,
-            earth_position: new_earth_pos,
-            earth_velocity: new_earth_vel,
-            moon_position: new_moon_pos,
-            moon_velocity: new_moon_vel,
+    fn continue_seek(&mut self) {
+        let (target, resume) = match &self.state {
+            State::Seeking(info) => (info.target, info.resume),
+            _ => return,
+        };
+        let integrator = self.integrator.get();
+        let interval = Checkpoints::INTERVAL.to_duration();
+        let deadline = Instant::now() + Self::SEEK_TIME_PER_FRAME;
+        // Go one checkpoint interval at a time, so that the checkpoints cover
+        // the whole way forward. Going backward only happens before the oldest
+        // checkpoint.
+        while self.current.timestamp != target && Instant::now() < deadline {
+            let t = self.current.timestamp;
+            if t < target {
+                let next = target.min(t + interval);
+                self.current = propagate(&self.current, integrator, next, Self::DEFAULT_STEP);
+                self.checkpoints.record(&self.current);
+            } else {
+                let next = target.max(t - interval);
+                self.current = propagate(&self.current, integrator, next, Self::DEFAULT_STEP);
+            }
+        }
+        if self.current.timestamp == target {
+            self.stop();
+            if resume {
+                self.start();
+            }
         }
     }
 
-    pub fn adjust_speed(&mut self, new_speed: Choice<chrono::Duration>) {
+    // Range of the simulation speed: from real time to a year per second.
+    pub const MIN_SPEED: Seconds = Seconds(1.0);
+    pub const MAX_SPEED: Seconds = Seconds(365.0 * 86400.0);
+
+    pub fn adjust_speed(&mut self, new_speed: chrono::Duration) {
+        let new_speed = Seconds::from(new_speed)
+            .at_least(Self::MIN_SPEED)
+            .at_most(Self::MAX_SPEED);
         // We need to stop and restart because advance assumes the
         // speed is unchanged since start.
-        self.stopped().speed = new_speed;
+        self.stopped().speed = new_speed.to_duration();
     }
 
     pub fn reverse(&mut self) {
@@ -155,9 +414,9 @@ impl Simulation {
     pub fn handle_event(&mut self, ev: &ControlEvent) {
         match ev {
             ControlEvent::StartStop => self.toggle_start(),
-            ControlEvent::SetSpeed(s) => self.adjust_speed(s.clone()),
-            ControlEvent::Faster => self.adjust_speed(self.speed.next()),
-            ControlEvent::Slower => self.adjust_speed(self.speed.prev()),
+            ControlEvent::SetSpeed(s) => self.adjust_speed(*s),
+            ControlEvent::Faster => self.adjust_speed(self.speed * 2),
+            ControlEvent::Slower => self.adjust_speed(self.speed / 2),
             ControlEvent::Reverse => {
                 self.reverse();
             }
@@ -165,30 +424,80 @@ impl Simulation {
                 let mut s = self.stopped();
                 let old_reverse = s.reverse;
                 s.reverse = matches!(ev, ControlEvent::JumpBack);
-                let simulation_speed_per_sec = Seconds::from(s.speed.get());
+                let simulation_speed_per_sec = Seconds::from(s.speed);
                 s.advance_by(simulation_speed_per_sec * 0.5, Self::MAX_STEPS_PER_FRAME);
                 s.reverse = old_reverse;
             }
+            ControlEvent::ToggleFixedStep => {
+                let pacing = match self.pacing {
+                    Pacing::Clock(_) => Pacing::FixedStep(Pacing::DEFAULT_FIXED_STEP),
+                    Pacing::FixedStep(_) => Pacing::Clock(Box::new(WallClock::new())),
+                };
+                self.set_pacing(pacing);
+            }
+            ControlEvent::FindEclipses => self.find_eclipses(),
+            ControlEvent::FindPhases => self.find_phases(),
+            ControlEvent::FindOrbitEvents => self.find_orbit_events(),
+            ControlEvent::SeekTo(timestamp) => self.seek_to(*timestamp),
+            ControlEvent::SetIntegrator(i) => self.set_integrator(i.clone()),
+            ControlEvent::CycleIntegrator => self.set_integrator(self.integrator.circular_next()),
             ControlEvent::LoadPreset(preset) => {
                 self.preset = preset.clone();
                 let mut s = self.stopped();
                 s.current = s.preset.snapshot;
+                s.initial_invariants = Invariants::new(&s.current);
+                s.cancel_searches();
+                s.checkpoints.reset(&s.current);
                 s.reverse = false;
+                if let Some(kind) = s.preset.integrator {
+                    s.integrator = s.integrator.choice_set().by_value(kind);
+                }
+                s.find_timeline_eclipses();
             }
+            ControlEvent::RestoreSession(session) => self.restore(session),
             _ => {}
         }
     }
 
+    fn set_integrator(&mut self, integrator: Choice<IntegratorKind>) {
+        let mut s = self.stopped();
+        s.integrator = integrator;
+        // The checkpoints after the current time were integrated with the
+        // previous integrator.
+        let current = s.current;
+        s.checkpoints.reset(&current);
+    }
+
+    // Restores the state saved in a session. The simulation is left stopped.
+    pub fn restore(&mut self, session: &Session) {
+        self.stop();
+        let presets = self.preset.choice_set();
+        if let Some(index) = presets.iter().position(|p| p.name == session.preset) {
+            self.preset = presets.by_index(index);
+        }
+        self.current = session.snapshot;
+        self.initial_invariants = Invariants::new(&self.current);
+        self.cancel_searches();
+        self.checkpoints.reset(&self.current);
+        self.reverse = session.reverse;
+        self.integrator = self.integrator.choice_set().by_value(session.integrator);
+        self.adjust_speed(session.speed);
+        self.find_timeline_eclipses();
+    }
+
     // stopped is used to stop the simulation and later restart it (if it was
-    // running).
+    // running). It cancels a seek in progress, restarting if the seek would
+    // have.
     fn stopped(&mut self) -> StoppedRef {
-        let was_running = self.is_running();
-        if was_running {
-            self.stop();
-        }
+        let needs_restart = match &self.state {
+            State::Stopped => false,
+            State::Running(_) => true,
+            State::Seeking(info) => info.resume,
+        };
+        self.stop();
         StoppedRef {
             sim: self,
-            needs_restart: was_running,
+            needs_restart,
         }
     }
 }
@@ -200,15 +509,73 @@ impl SimulationState for Simulation {
     fn timestamp(&self) -> DateTime<Utc> {
         self.current.timestamp
     }
+    fn snapshot(&self) -> Snapshot {
+        self.current
+    }
     fn is_running(&self) -> bool {
         matches!(self.state, State::Running(..))
     }
-    fn speed(&self) -> Choice<chrono::Duration> {
-        self.speed.clone()
+    fn speed(&self) -> chrono::Duration {
+        self.speed
     }
     fn is_reverse(&self) -> bool {
         self.reverse
     }
+    fn is_fixed_step(&self) -> bool {
+        matches!(self.pacing, Pacing::FixedStep(_))
+    }
+    fn integrator(&self) -> Choice<IntegratorKind> {
+        self.integrator.clone()
+    }
+    fn drift(&self) -> Drift {
+        Drift::new(&self.initial_invariants, &Invariants::new(&self.current))
+    }
+    fn eclipses(&self) -> &[Eclipse] {
+        &self.eclipses
+    }
+    fn phases(&self) -> &[PhaseEvent] {
+        &self.phases
+    }
+    fn orbit_events(&self) -> &[OrbitEvent] {
+        &self.orbit_events
+    }
+    fn timeline_eclipses(&self) -> &[Eclipse] {
+        &self.timeline_eclipses
+    }
+    fn seek_progress(&self) -> Option<f64> {
+        match &self.state {
+            State::Seeking(info) => {
+                let total = Seconds::from(info.target - info.from).0;
+                let left = Seconds::from(info.target - self.current.timestamp).0;
+                Some(if total == 0.0 {
+                    1.0
+                } else {
+                    1.0 - left / total
+                })
+            }
+            _ => None,
+        }
+    }
+    fn search_progress(&self, kind: SearchKind) -> Option<f64> {
+        match kind {
+            SearchKind::Eclipses => self.eclipse_search.as_ref().map(Search::progress),
+            SearchKind::Phases => self.phase_search.as_ref().map(Search::progress),
+            SearchKind::OrbitEvents => self.orbit_search.as_ref().map(Search::progress),
+        }
+    }
+}
+
+// Continues the search until it is done or the deadline passes, adding the
+// events found to the results.
+fn continue_search<T>(search: &mut Option<Search<T>>, found: &mut Vec<T>, deadline: Instant) {
+    if let Some(s) = search {
+        while !s.is_done() && Instant::now() < deadline {
+            found.extend(s.step());
+        }
+        if s.is_done() {
+            *search = None;
+        }
+    }
 }
 
 // StoppedRef is used internally to temporarily stop the simulation to make changes.
@@ -239,28 +606,265 @@ impl<'a> DerefMut for StoppedRef<'a> {
     }
 }
 
-fn gacc_earth_and_moon(
-    earth_position: &Point3<f64>,
-    moon_position: &Point3<f64>,
-) -> (Vector3<f64>, Vector3<f64>) {
-    let sun_pos = Point3::<f64>::new(0.0, 0.0, 0.0);
+// Integrates the snapshot to the target timestamp, using equal steps of at most
+// max_step. The result only depends on the arguments.
+pub fn propagate(
+    snapshot: &Snapshot,
+    integrator: IntegratorKind,
+    target: DateTime<Utc>,
+    max_step: Seconds,
+) -> Snapshot {
+    let delta = Seconds::between(&snapshot.timestamp, &target);
+    let num_steps = (delta.0.abs() / max_step.0).ceil();
+    let mut result = *snapshot;
+    if num_steps > 0.0 {
+        let step = delta / num_steps;
+        for _ in 0..num_steps as u64 {
+            integrator.integrator().step(&mut result.bodies, step.0);
+        }
+    }
+    // Set the timestamp directly to avoid accumulating rounding errors.
+    result.timestamp = target;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{body::Body, choice::ChoiceSet, config::load_preset};
+
+    fn simulation() -> Simulation {
+        let preset = Preset {
+            name: "Test".to_string(),
+            snapshot: load_preset("2017-08-21-solar-eclipse.toml"),
+            integrator: None,
+            camera: None,
+            observer: None,
+            span: chrono::Duration::days(Preset::DEFAULT_SPAN_DAYS),
+        };
+        Simulation::new(
+            &ChoiceSet::new([preset]).by_index(0),
+            chrono::Duration::days(1),
+            &ChoiceSet::new(IntegratorKind::ALL).by_value(IntegratorKind::Rk4),
+        )
+    }
 
-    let earth_acc = gacc(earth_position, &sun_pos, BodyProperties::SUN.mass)
-        + gacc(earth_position, moon_position, BodyProperties::MOON.mass);
+    // Seeks and waits until done.
+    fn seek(sim: &mut Simulation, timestamp: DateTime<Utc>) {
+        sim.seek_to(timestamp);
+        while sim.seek_progress().is_some() {
+            sim.advance();
+        }
+        assert_eq!(sim.current().timestamp, timestamp);
+    }
 
-    let moon_acc = gacc(moon_position, &sun_pos, BodyProperties::SUN.mass)
-        + gacc(moon_position, earth_position, BodyProperties::EARTH.mass);
+    fn distance(a: &Snapshot, b: &Snapshot) -> f64 {
+        Body::ALL
+            .iter()
+            .map(|body| (a.position(*body) - b.position(*body)).norm())
+            .fold(0.0, f64::max)
+    }
 
-    (earth_acc, moon_acc)
-}
+    #[test]
+    fn rewind() {
+        let mut sim = simulation();
+        let start = sim.current();
+        let day = chrono::Duration::days(1);
+        seek(&mut sim, start.timestamp + day * 2);
+        let two_days = sim.current();
+        seek(&mut sim, start.timestamp + day * 3);
+
+        // Going back to a checkpoint restores it exactly.
+        seek(&mut sim, start.timestamp);
+        assert_eq!(distance(&sim.current(), &start), 0.0);
+        // And so does going forward again.
+        seek(&mut sim, two_days.timestamp);
+        assert_eq!(distance(&sim.current(), &two_days), 0.0);
+
+        // Reversing restarts from the checkpoints.
+        seek(&mut sim, start.timestamp + day * 3);
+        sim.reverse = true;
+        sim.advance_by(Seconds(24.5 * 3600.0), 1);
+        let expected = propagate(
+            &start,
+            IntegratorKind::Rk4,
+            sim.current().timestamp,
+            Simulation::DEFAULT_STEP,
+        );
+        assert!(distance(&sim.current(), &expected) < 1e-3);
+        // One checkpoint per hour.
+        assert_eq!(sim.checkpoints.len(), 73);
+
+        // Before the oldest checkpoint, integrate backwards.
+        seek(&mut sim, start.timestamp - day);
+        assert_eq!(sim.current().timestamp, start.timestamp - day);
+        sim.advance_by(Seconds::from(day), 1);
+        assert!(sim.current().timestamp < start.timestamp - day);
+        assert_eq!(sim.checkpoints.len(), 73);
+    }
+
+    #[test]
+    fn change_integrator() {
+        let mut sim = simulation();
+        let start = sim.current();
+        let day = chrono::Duration::days(1);
+        seek(&mut sim, start.timestamp + day * 2);
+        seek(&mut sim, start.timestamp);
+
+        // Going forward again doesn't reuse the states integrated with RK4.
+        let yoshida = sim
+            .integrator
+            .choice_set()
+            .by_value(IntegratorKind::Yoshida4);
+        sim.handle_event(&ControlEvent::SetIntegrator(yoshida));
+        assert_eq!(sim.checkpoints.len(), 1);
+        seek(&mut sim, start.timestamp + day * 2);
+        let expected = propagate(
+            &start,
+            IntegratorKind::Yoshida4,
+            start.timestamp + day * 2,
+            Simulation::DEFAULT_STEP,
+        );
+        assert!(distance(&sim.current(), &expected) < 1e-6);
+
+        // Neither does going back.
+        sim.handle_event(&ControlEvent::CycleIntegrator);
+        assert_eq!(sim.integrator.get(), IntegratorKind::Rkf45);
+        sim.reverse = true;
+        sim.advance_by(Seconds::from(day), 1);
+        assert_eq!(sim.checkpoints.len(), 1);
+    }
+
+    #[test]
+    fn long_seek() {
+        let mut sim = simulation();
+        let start = sim.current().timestamp;
+        sim.start();
+        seek(&mut sim, start + chrono::Duration::days(200));
+        // The simulation runs again when done.
+        assert!(sim.is_running());
+
+        sim.stop();
+        seek(&mut sim, start - chrono::Duration::days(20));
+        assert!(!sim.is_running());
+    }
+
+    #[test]
+    fn search() {
+        let mut sim = simulation();
+        let start = sim.current();
+        sim.find_phases();
+        // The search is done in the following frames.
+        assert_eq!(sim.search_progress(SearchKind::Phases), Some(0.0));
+        assert!(sim.phases().is_empty());
+        sim.finish_searches();
+        assert_eq!(sim.search_progress(SearchKind::Phases), None);
+        let end = start.timestamp + chrono::Duration::days(Simulation::PHASE_SEARCH_DAYS);
+        let expected = lunar::find_phases(&start, IntegratorKind::Rk4, end, Seconds(60.0));
+        assert_eq!(sim.phases().len(), expected.len());
+
+        // Loading a preset cancels the search.
+        sim.find_orbit_events();
+        sim.handle_event(&ControlEvent::LoadPreset(sim.preset.clone()));
+        assert_eq!(sim.search_progress(SearchKind::OrbitEvents), None);
+        assert!(sim.phases().is_empty());
+    }
 
-const G: f64 = 6.67430e-11; // N*m^2/kg^2
+    #[test]
+    fn timeline() {
+        let mut sim = simulation();
+        let start = sim.current().timestamp;
+        sim.finish_searches();
+        // The six eclipses of the year after the preset (see eclipse::find),
+        // searched without Find.
+        let eclipses = sim.timeline_eclipses();
+        assert_eq!(eclipses.len(), 6);
+        let span = start..=start + chrono::Duration::days(Preset::DEFAULT_SPAN_DAYS);
+        assert!(eclipses.iter().all(|e| span.contains(&e.greatest)));
+        assert!(sim.eclipses().is_empty());
+    }
+
+    #[test]
+    fn distant_seek() {
+        // More than 292 years away, which doesn't fit in i64 nanoseconds.
+        let mut sim = simulation();
+        let target = crate::time::parse_utc("2400-01-01 00:00").unwrap();
+        sim.seek_to(target);
+        sim.advance();
+        let progress = sim.seek_progress().unwrap();
+        assert!(progress > 0.0 && progress < 0.01, "{}", progress);
+    }
+
+    #[test]
+    fn cancel_seek() {
+        let mut sim = simulation();
+        let start = sim.current().timestamp;
+        sim.seek_to(start + chrono::Duration::days(3000));
+        assert!(sim.seek_progress().is_some());
+
+        // Loading a preset stops the seek.
+        sim.handle_event(&ControlEvent::LoadPreset(sim.preset.clone()));
+        assert_eq!(sim.seek_progress(), None);
+        sim.advance();
+        assert_eq!(sim.current().timestamp, start);
+        assert!(!sim.is_running());
+
+        // So do the other changes, which restart the simulation if the seek
+        // would have.
+        sim.start();
+        sim.seek_to(start + chrono::Duration::days(3000));
+        sim.handle_event(&ControlEvent::JumpForward);
+        assert_eq!(sim.seek_progress(), None);
+        assert!(sim.is_running());
+        sim.seek_to(start + chrono::Duration::days(3000));
+        sim.handle_event(&ControlEvent::CycleIntegrator);
+        assert_eq!(sim.seek_progress(), None);
+        assert!(sim.is_running());
+    }
+
+    #[test]
+    fn deterministic() {
+        // Two runs with the same clock readings give identical results, even
+        // with uneven frames.
+        let run = || {
+            let mut sim = simulation();
+            let clock = clock::ManualClock::new();
+            sim.set_pacing(Pacing::Clock(Box::new(clock.clone())));
+            sim.start();
+            for i in 0..50 {
+                clock.advance(Duration::from_millis(10 + i * 7 % 23));
+                sim.advance();
+            }
+            sim.current()
+        };
+        let (a, b) = (run(), run());
+        assert_eq!(a.timestamp, b.timestamp);
+        assert_eq!(distance(&a, &b), 0.0);
+    }
+
+    #[test]
+    fn fixed_step() {
+        let mut sim = simulation();
+        let start = sim.current().timestamp;
+        sim.set_pacing(Pacing::FixedStep(Duration::from_millis(250)));
+        sim.start();
+        for _ in 0..8 {
+            sim.advance();
+        }
+        // Two seconds at one day per second.
+        let dt = sim.current().timestamp - (start + chrono::Duration::days(2));
+        assert!(dt.num_microseconds().unwrap().abs() < 1, "{}", dt);
+        assert!(sim.is_fixed_step());
+    }
 
-// Returns the acceleration vector due to gravity as a vector (with km/s^2 components).
-fn gacc(pos: &Point3<f64>, other_pos: &Point3<f64>, other_mass: f64) -> Vector3<f64> {
-    let vec = other_pos - pos;
-    // The 1e-9 adjustment is km^2 -> m^2 conversion for the denominator
-    // and m -> km conversion for the result.
-    let amount = G * other_mass / vec.norm_squared() * 1e-9;
-    vec.normalize() * amount
+    #[test]
+    fn speed() {
+        let mut sim = simulation();
+        sim.handle_event(&ControlEvent::Faster);
+        assert_eq!(sim.speed(), chrono::Duration::days(2));
+        sim.handle_event(&ControlEvent::SetSpeed(chrono::Duration::days(1000)));
+        assert_eq!(Seconds::from(sim.speed()).0, Simulation::MAX_SPEED.0);
+        sim.handle_event(&ControlEvent::SetSpeed(chrono::Duration::milliseconds(1)));
+        assert_eq!(sim.speed(), chrono::Duration::seconds(1));
+    }
 }
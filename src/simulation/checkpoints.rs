@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+use super::{Seconds, Snapshot};
+
+// A ring buffer of snapshots recorded periodically while the simulation moves
+// forward. Going back in time restarts from the latest checkpoint before the
+// target time, instead of integrating backwards, so that earlier states are
+// found again exactly. Checkpoints after the current time are kept, so that
+// moving forward again over the same span is fast.
+pub struct Checkpoints {
+    // Ordered by timestamp.
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl Checkpoints {
+    // Simulated time between checkpoints.
+    pub const INTERVAL: Seconds = Seconds(3600.0);
+    // The buffer covers a bit more than a year.
+    const CAPACITY: usize = 10_000;
+
+    pub fn new(initial: &Snapshot) -> Self {
+        let mut res = Self {
+            snapshots: VecDeque::new(),
+        };
+        res.reset(initial);
+        res
+    }
+
+    // Drops all the checkpoints, e.g. when the simulation jumps to an
+    // unrelated state.
+    pub fn reset(&mut self, initial: &Snapshot) {
+        self.snapshots.clear();
+        self.snapshots.push_back(*initial);
+    }
+
+    // Records the snapshot if it is at least INTERVAL after the latest
+    // checkpoint. Snapshots within the span already covered are ignored.
+    pub fn record(&mut self, snapshot: &Snapshot) {
+        if let Some(last) = self.snapshots.back() {
+            if Seconds::from(snapshot.timestamp - last.timestamp).0 < Self::INTERVAL.0 {
+                return;
+            }
+        }
+        if self.snapshots.len() == Self::CAPACITY {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(*snapshot);
+    }
+
+    // Returns the latest checkpoint at or before the target time, or None if
+    // the target is before the oldest checkpoint.
+    pub fn latest_before(&self, target: DateTime<Utc>) -> Option<&Snapshot> {
+        let index = self.snapshots.partition_point(|s| s.timestamp <= target);
+        index.checked_sub(1).map(|i| &self.snapshots[i])
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::parse_utc;
+
+    fn snapshot(hours: f64) -> Snapshot {
+        let t0 = parse_utc("2017-08-21 00:00").unwrap();
+        let millis = (hours * 3600e3) as i64;
+        Snapshot::new(t0 + chrono::Duration::milliseconds(millis), [])
+    }
+
+    #[test]
+    fn record() {
+        let mut c = Checkpoints::new(&snapshot(0.0));
+        for i in 1..=40 {
+            c.record(&snapshot(i as f64 * 0.25));
+        }
+        // One checkpoint per hour.
+        assert_eq!(c.len(), 11);
+
+        let at = |c: &Checkpoints, hours| {
+            c.latest_before(snapshot(hours).timestamp)
+                .map(|s| s.timestamp)
+        };
+        assert_eq!(at(&c, 5.5), Some(snapshot(5.0).timestamp));
+        assert_eq!(at(&c, 0.0), Some(snapshot(0.0).timestamp));
+        assert_eq!(at(&c, 20.0), Some(snapshot(10.0).timestamp));
+        assert_eq!(at(&c, -1.0), None);
+
+        // Going forward again over the covered span doesn't add checkpoints.
+        c.record(&snapshot(5.75));
+        c.record(&snapshot(6.5));
+        assert_eq!(c.len(), 11);
+        c.record(&snapshot(10.5));
+        c.record(&snapshot(11.0));
+        assert_eq!(c.len(), 12);
+    }
+
+    #[test]
+    fn capacity() {
+        let mut c = Checkpoints::new(&snapshot(0.0));
+        for i in 1..=Checkpoints::CAPACITY + 5 {
+            c.record(&snapshot(i as f64));
+        }
+        assert_eq!(c.len(), Checkpoints::CAPACITY);
+        // The oldest ones are dropped.
+        assert!(c.latest_before(snapshot(5.5).timestamp).is_none());
+        assert!(c.latest_before(snapshot(6.0).timestamp).is_some());
+    }
+}
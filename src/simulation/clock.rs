@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+#[cfg(test)]
+use std::{cell::Cell, rc::Rc};
+
+// Source of the time that drives the simulation while it runs. Replacing the
+// wall clock makes runs repeatable.
+pub trait Clock {
+    // Time elapsed since an arbitrary origin.
+    fn now(&self) -> Duration;
+}
+
+pub struct WallClock {
+    origin: Instant,
+}
+
+impl WallClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for WallClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+// A clock that only moves when told to. Clones share the same time, so one can
+// be given to the simulation and the other kept to move it.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, d: Duration) {
+        self.now.set(self.now.get() + d);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+// How the simulated time follows the frames while the simulation runs.
+pub enum Pacing {
+    // The simulated time follows the clock: each frame moves the simulation
+    // by the time elapsed since the previous one.
+    Clock(Box<dyn Clock>),
+    // Each frame moves the simulation as if the given duration elapsed,
+    // regardless of the frame rate.
+    FixedStep(Duration),
+}
+
+impl Pacing {
+    // One frame per refresh of a 60 Hz display.
+    pub const DEFAULT_FIXED_STEP: Duration = Duration::from_micros(16_667);
+}
@@ -0,0 +1,114 @@
+use kiss3d::nalgebra::Vector3;
+
+use crate::body::Body;
+
+use super::Snapshot;
+
+// Quantities that are conserved by the exact solution. Any change over time is
+// caused by integration error.
+//
+// All quantities use the simulation units (kg, km, s); the energy is in
+// kg*km^2/s^2 (MJ).
+#[derive(Copy, Clone, Debug)]
+pub struct Invariants {
+    pub energy: f64,
+    pub momentum: Vector3<f64>,
+    // Angular momentum around the origin.
+    pub angular_momentum: Vector3<f64>,
+    // Sum of the magnitudes of the body momenta. Used to scale the momentum
+    // drift, since the total momentum is normally zero.
+    pub momentum_scale: f64,
+}
+
+impl Invariants {
+    pub fn new(snapshot: &Snapshot) -> Self {
+        let mut kinetic = 0.0;
+        let mut potential = 0.0;
+        let mut momentum = Vector3::zeros();
+        let mut angular_momentum = Vector3::zeros();
+        let mut momentum_scale = 0.0;
+        for (i, body) in Body::ALL.iter().enumerate() {
+            let s = snapshot.state(*body);
+            let m = body.mass();
+            kinetic += 0.5 * m * s.velocity.norm_squared();
+            momentum += s.velocity * m;
+            angular_momentum += s.position.coords.cross(&(s.velocity * m));
+            momentum_scale += s.velocity.norm() * m;
+
+            for other in &Body::ALL[i + 1..] {
+                let r = (snapshot.position(*other) - s.position).norm();
+                potential -= m * other.gm() / r;
+            }
+        }
+        Self {
+            energy: kinetic + potential,
+            momentum,
+            angular_momentum,
+            momentum_scale,
+        }
+    }
+}
+
+// Relative change of the invariants since a reference snapshot.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Drift {
+    pub energy: f64,
+    pub momentum: f64,
+    pub angular_momentum: f64,
+}
+
+impl Drift {
+    pub fn new(initial: &Invariants, current: &Invariants) -> Self {
+        Self {
+            energy: ((current.energy - initial.energy) / initial.energy).abs(),
+            momentum: (current.momentum - initial.momentum).norm() / initial.momentum_scale,
+            angular_momentum: (current.angular_momentum - initial.angular_momentum).norm()
+                / initial.angular_momentum.norm(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::load_preset, simulation::IntegratorKind};
+
+    fn drift_after(kind: IntegratorKind, dt: f64, num_steps: usize) -> Drift {
+        let start = load_preset("2022-05-16-lunar-eclipse.toml");
+        let mut s = start;
+        for _ in 0..num_steps {
+            kind.integrator().step(&mut s.bodies, dt);
+        }
+        Drift::new(&Invariants::new(&start), &Invariants::new(&s))
+    }
+
+    #[test]
+    fn no_drift_at_start() {
+        let s = load_preset("2017-08-21-solar-eclipse.toml");
+        let d = Drift::new(&Invariants::new(&s), &Invariants::new(&s));
+        assert_eq!(d.energy, 0.0);
+        assert_eq!(d.momentum, 0.0);
+        assert_eq!(d.angular_momentum, 0.0);
+    }
+
+    #[test]
+    fn drift() {
+        // 90 days with 10 minute steps.
+        for (kind, max_energy_drift) in [
+            (IntegratorKind::VelocityVerlet, 1e-9),
+            (IntegratorKind::Rk4, 1e-12),
+            (IntegratorKind::Yoshida4, 1e-12),
+            (IntegratorKind::Rkf45, 1e-12),
+        ] {
+            let d = drift_after(kind, 600.0, 90 * 144);
+            assert!(
+                d.energy < max_energy_drift,
+                "{}: energy drift {}",
+                kind.name(),
+                d.energy
+            );
+            assert!(d.momentum < 1e-13, "{}: {:?}", kind.name(), d);
+            assert!(d.angular_momentum < 1e-12, "{}: {:?}", kind.name(), d);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use kiss3d::nalgebra::{Point3, Vector3};
+
+use crate::body::Body;
+
+// Returns the gravitational acceleration of each body, caused by all the other
+// bodies. The positions are indexed by Body::index().
+pub fn accelerations(positions: &[Point3<f64>; Body::COUNT]) -> [Vector3<f64>; Body::COUNT] {
+    let mut result = [Vector3::zeros(); Body::COUNT];
+    for body in Body::ALL {
+        let pos = &positions[body.index()];
+        for other in Body::ALL {
+            if other != body {
+                result[body.index()] += gacc(pos, &positions[other.index()], other.gm());
+            }
+        }
+    }
+    result
+}
+
+// Returns the acceleration vector due to gravity as a vector (with km/s^2
+// components), given the gravitational parameter of the other body.
+fn gacc(pos: &Point3<f64>, other_pos: &Point3<f64>, other_gm: f64) -> Vector3<f64> {
+    let vec = other_pos - pos;
+    let amount = other_gm / vec.norm_squared();
+    vec.normalize() * amount
+}
@@ -0,0 +1,313 @@
+use kiss3d::nalgebra::Vector3;
+
+use crate::body::Body;
+
+use super::{gravity::accelerations, snapshot::BodyState};
+
+type Bodies = [BodyState; Body::COUNT];
+
+// Integrator is a numerical method that advances the state of the bodies.
+pub trait Integrator {
+    // Advances the bodies by dt seconds. The step can be negative.
+    fn step(&self, bodies: &mut Bodies, dt: f64);
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum IntegratorKind {
+    VelocityVerlet,
+    Rk4,
+    Yoshida4,
+    Rkf45,
+}
+
+impl IntegratorKind {
+    pub const ALL: [IntegratorKind; 4] = [
+        IntegratorKind::VelocityVerlet,
+        IntegratorKind::Rk4,
+        IntegratorKind::Yoshida4,
+        IntegratorKind::Rkf45,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::VelocityVerlet => "Verlet",
+            Self::Rk4 => "RK4",
+            Self::Yoshida4 => "Yoshida",
+            Self::Rkf45 => "RKF45",
+        }
+    }
+
+    // Looks up an integrator by name (case insensitive).
+    pub fn from_name(name: &str) -> Option<IntegratorKind> {
+        Self::ALL
+            .into_iter()
+            .find(|k| k.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn integrator(&self) -> &'static dyn Integrator {
+        match self {
+            Self::VelocityVerlet => &VelocityVerlet,
+            Self::Rk4 => &Rk4,
+            Self::Yoshida4 => &Yoshida4,
+            Self::Rkf45 => &Rkf45::DEFAULT,
+        }
+    }
+}
+
+// Velocity Verlet integration (2nd order, symplectic):
+//   x(t+dt) = x(t) + v(t)dt + a(t)dt^2/2
+//   v(t+dt) = v(t) + ((a(t) + a(t+dt))dt/2
+pub struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn step(&self, bodies: &mut Bodies, dt: f64) {
+        let acc = accelerations(&bodies.map(|b| b.position));
+        for (b, a) in bodies.iter_mut().zip(acc.iter()) {
+            b.position += b.velocity * dt + a * (0.5 * dt * dt);
+        }
+
+        let new_acc = accelerations(&bodies.map(|b| b.position));
+        for ((b, a), new_a) in bodies.iter_mut().zip(acc.iter()).zip(new_acc.iter()) {
+            b.velocity += (a + new_a) * (0.5 * dt);
+        }
+    }
+}
+
+// Classic 4th order Runge-Kutta integration.
+pub struct Rk4;
+
+impl Rk4 {
+    const A: [&'static [f64]; 4] = [&[], &[0.5], &[0.0, 0.5], &[0.0, 0.0, 1.0]];
+    const B: [f64; 4] = [1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0];
+}
+
+impl Integrator for Rk4 {
+    fn step(&self, bodies: &mut Bodies, dt: f64) {
+        let k = rk_stages(bodies, dt, &Self::A);
+        *bodies = combine(bodies, dt, &k, &Self::B);
+    }
+}
+
+// Yoshida's 4th order symplectic integrator: a composition of three leapfrog
+// steps with carefully chosen lengths. Unlike RK4, the energy error does not
+// grow over long runs.
+pub struct Yoshida4;
+
+impl Integrator for Yoshida4 {
+    fn step(&self, bodies: &mut Bodies, dt: f64) {
+        let w1 = 1.0 / (2.0 - 2f64.cbrt());
+        let w0 = -2f64.cbrt() * w1;
+        // Drift (position) and kick (velocity) coefficients.
+        let c = [0.5 * w1, 0.5 * (w0 + w1), 0.5 * (w0 + w1), 0.5 * w1];
+        let d = [w1, w0, w1];
+
+        for i in 0..c.len() {
+            for b in bodies.iter_mut() {
+                b.position += b.velocity * (c[i] * dt);
+            }
+            if i < d.len() {
+                let acc = accelerations(&bodies.map(|b| b.position));
+                for (b, a) in bodies.iter_mut().zip(acc.iter()) {
+                    b.velocity += a * (d[i] * dt);
+                }
+            }
+        }
+    }
+}
+
+// Runge-Kutta-Fehlberg 4(5) integration with adaptive step size. Each step is
+// internally split into smaller steps as necessary to keep the estimated
+// position error under the tolerance.
+pub struct Rkf45 {
+    // Maximum estimated position error (in km) for each internal step.
+    pub tolerance: f64,
+}
+
+impl Rkf45 {
+    pub const DEFAULT: Rkf45 = Rkf45 { tolerance: 1e-6 };
+
+    // Internal steps are never smaller than this (in seconds).
+    const MIN_STEP: f64 = 1e-3;
+
+    const A: [&'static [f64]; 6] = [
+        &[],
+        &[1.0 / 4.0],
+        &[3.0 / 32.0, 9.0 / 32.0],
+        &[1932.0 / 2197.0, -7200.0 / 2197.0, 7296.0 / 2197.0],
+        &[439.0 / 216.0, -8.0, 3680.0 / 513.0, -845.0 / 4104.0],
+        &[
+            -8.0 / 27.0,
+            2.0,
+            -3544.0 / 2565.0,
+            1859.0 / 4104.0,
+            -11.0 / 40.0,
+        ],
+    ];
+    // Weights for the 5th order solution.
+    const B5: [f64; 6] = [
+        16.0 / 135.0,
+        0.0,
+        6656.0 / 12825.0,
+        28561.0 / 56430.0,
+        -9.0 / 50.0,
+        2.0 / 55.0,
+    ];
+    // Weights for the 4th order solution.
+    const B4: [f64; 6] = [
+        25.0 / 216.0,
+        0.0,
+        1408.0 / 2565.0,
+        2197.0 / 4104.0,
+        -1.0 / 5.0,
+        0.0,
+    ];
+}
+
+impl Integrator for Rkf45 {
+    fn step(&self, bodies: &mut Bodies, dt: f64) {
+        let mut remaining = dt;
+        let mut h = dt;
+        while remaining != 0.0 {
+            if h.abs() > remaining.abs() {
+                h = remaining;
+            }
+            let k = rk_stages(bodies, h, &Self::A);
+            let high = combine(bodies, h, &k, &Self::B5);
+            let low = combine(bodies, h, &k, &Self::B4);
+            let err = high
+                .iter()
+                .zip(low.iter())
+                .map(|(a, b)| (a.position - b.position).norm())
+                .fold(0.0, f64::max);
+
+            if err <= self.tolerance || h.abs() <= Self::MIN_STEP {
+                *bodies = high;
+                remaining -= h;
+            }
+
+            // Adjust the step size for the next step (or the retry).
+            let factor = if err == 0.0 {
+                4.0
+            } else {
+                (0.9 * (self.tolerance / err).powf(0.2)).clamp(0.1, 4.0)
+            };
+            h = (h * factor).abs().max(Self::MIN_STEP) * dt.signum();
+        }
+    }
+}
+
+// Derivative of the state of each body: (velocity, acceleration).
+type Derivative = [(Vector3<f64>, Vector3<f64>); Body::COUNT];
+
+fn derivative(bodies: &Bodies) -> Derivative {
+    let acc = accelerations(&bodies.map(|b| b.position));
+    let mut result = [(Vector3::zeros(), Vector3::zeros()); Body::COUNT];
+    for (r, (b, a)) in result.iter_mut().zip(bodies.iter().zip(acc.iter())) {
+        *r = (b.velocity, *a);
+    }
+    result
+}
+
+// Returns the state advanced by dt * sum(weights[i] * k[i]).
+fn combine(bodies: &Bodies, dt: f64, k: &[Derivative], weights: &[f64]) -> Bodies {
+    let mut result = *bodies;
+    for (ki, &w) in k.iter().zip(weights.iter()) {
+        if w == 0.0 {
+            continue;
+        }
+        for (b, (dx, dv)) in result.iter_mut().zip(ki.iter()) {
+            b.position += dx * (w * dt);
+            b.velocity += dv * (w * dt);
+        }
+    }
+    result
+}
+
+// Calculates the stages of an explicit Runge-Kutta method, given the rows of
+// the Butcher tableau.
+fn rk_stages(bodies: &Bodies, dt: f64, a: &[&[f64]]) -> Vec<Derivative> {
+    let mut k = Vec::with_capacity(a.len());
+    for row in a {
+        let d = derivative(&combine(bodies, dt, &k, row));
+        k.push(d);
+    }
+    k
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_preset;
+
+    fn run(kind: IntegratorKind, bodies: &Bodies, dt: f64, num_steps: usize) -> Bodies {
+        let mut bodies = *bodies;
+        for _ in 0..num_steps {
+            kind.integrator().step(&mut bodies, dt);
+        }
+        bodies
+    }
+
+    #[test]
+    fn accuracy() {
+        let start = load_preset("2017-08-21-solar-eclipse.toml").bodies;
+        // Reference solution: one day with 10 second steps.
+        let reference = run(IntegratorKind::Rk4, &start, 10.0, 8640);
+
+        for (kind, max_err) in [
+            (IntegratorKind::VelocityVerlet, 5.0),
+            (IntegratorKind::Rk4, 1e-4),
+            (IntegratorKind::Yoshida4, 1e-3),
+            (IntegratorKind::Rkf45, 1e-4),
+        ] {
+            // One day with one hour steps.
+            let result = run(kind, &start, 3600.0, 24);
+            for body in [Body::Earth, Body::Moon] {
+                let err = (result[body.index()].position - reference[body.index()].position).norm();
+                assert!(
+                    err < max_err,
+                    "{} {:?}: error {} km",
+                    kind.name(),
+                    body,
+                    err
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn momentum() {
+        // The snapshot is in the barycentric frame, so the total momentum is
+        // zero and it should remain zero.
+        let start = load_preset("2017-08-21-solar-eclipse.toml").bodies;
+        let earth_momentum = start[Body::Earth.index()].velocity.norm() * Body::Earth.mass();
+        for kind in IntegratorKind::ALL {
+            let result = run(kind, &start, 3600.0, 24 * 30);
+            let momentum: Vector3<f64> = Body::ALL
+                .iter()
+                .map(|b| result[b.index()].velocity * b.mass())
+                .sum();
+            assert!(
+                momentum.norm() < earth_momentum * 1e-12,
+                "{}: momentum {}",
+                kind.name(),
+                momentum.norm()
+            );
+            // The Sun is not stationary.
+            let sun_delta = result[Body::Sun.index()].position - start[Body::Sun.index()].position;
+            assert!(sun_delta.norm() > 1.0);
+        }
+    }
+
+    #[test]
+    fn reversible() {
+        let start = load_preset("2022-05-16-lunar-eclipse.toml").bodies;
+        for kind in IntegratorKind::ALL {
+            let forward = run(kind, &start, 600.0, 144);
+            let back = run(kind, &forward, -600.0, 144);
+            for body in [Body::Earth, Body::Moon] {
+                let err = (back[body.index()].position - start[body.index()].position).norm();
+                assert!(err < 1e-3, "{} {:?}: error {} km", kind.name(), body, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+
+use super::{propagate, IntegratorKind, Seconds, Snapshot};
+
+// Finds the events of some kind (e.g. eclipse::find_eclipses) between the
+// snapshot time and a later time, in chronological order.
+pub type FindFn<T> = fn(&Snapshot, IntegratorKind, DateTime<Utc>, Seconds) -> Vec<T>;
+
+// The kinds of events that the simulation searches for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchKind {
+    Eclipses,
+    Phases,
+    OrbitEvents,
+}
+
+// A search for events over a long time range, done one chunk at a time so
+// that it can be spread over several frames. It finds the same events as
+// searching the whole range at once.
+pub struct Search<T> {
+    find: FindFn<T>,
+    // Time of an event.
+    time: fn(&T) -> DateTime<Utc>,
+    integrator: IntegratorKind,
+    max_step: Seconds,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    // Start of the next chunk.
+    next: DateTime<Utc>,
+    // State at the start of the next chunk, minus the margin.
+    origin: Snapshot,
+}
+
+impl<T> Search<T> {
+    // Simulated time covered by each chunk.
+    const CHUNK: Seconds = Seconds(10.0 * 86400.0);
+    // Each chunk is searched with this margin on both sides, because the
+    // events are found between samples (up to 6 hours apart); events in the
+    // margins are left to the neighboring chunks.
+    const MARGIN: Seconds = Seconds(12.0 * 3600.0);
+
+    pub fn new(
+        find: FindFn<T>,
+        time: fn(&T) -> DateTime<Utc>,
+        snapshot: &Snapshot,
+        integrator: IntegratorKind,
+        end: DateTime<Utc>,
+        max_step: Seconds,
+    ) -> Self {
+        Self {
+            find,
+            time,
+            integrator,
+            max_step,
+            start: snapshot.timestamp,
+            end,
+            next: snapshot.timestamp,
+            origin: *snapshot,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.end
+    }
+
+    // Fraction of the time range that is searched.
+    pub fn progress(&self) -> f64 {
+        let total = Seconds::from(self.end - self.start).0;
+        if total <= 0.0 {
+            1.0
+        } else {
+            Seconds::from(self.next - self.start).0 / total
+        }
+    }
+
+    // Searches the next chunk and returns the events found in it, in
+    // chronological order.
+    pub fn step(&mut self) -> Vec<T> {
+        if self.is_done() {
+            return Vec::new();
+        }
+        let (start, end) = (
+            self.next,
+            (self.next + Self::CHUNK.to_duration()).min(self.end),
+        );
+        let is_last = end == self.end;
+        let mut events = (self.find)(
+            &self.origin,
+            self.integrator,
+            (end + Self::MARGIN.to_duration()).min(self.end),
+            self.max_step,
+        );
+        events.retain(|e| {
+            let t = (self.time)(e);
+            t >= start && (t < end || is_last)
+        });
+
+        self.next = end;
+        if !self.is_done() {
+            let origin = (end - Self::MARGIN.to_duration()).max(self.start);
+            self.origin = propagate(&self.origin, self.integrator, origin, self.max_step);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::load_preset,
+        lunar::{self, OrbitEvent, PhaseEvent},
+    };
+
+    // Searches in chunks and compares with a search of the whole range.
+    fn compare<T>(find: FindFn<T>, time: fn(&T) -> DateTime<Utc>) {
+        let start = load_preset("2017-08-21-solar-eclipse.toml");
+        let end = start.timestamp + chrono::Duration::days(45);
+        let integrator = IntegratorKind::Rk4;
+        let expected = find(&start, integrator, end, Seconds(60.0));
+
+        let mut search = Search::new(find, time, &start, integrator, end, Seconds(60.0));
+        let mut found = Vec::new();
+        let mut progress = 0.0;
+        while !search.is_done() {
+            found.extend(search.step());
+            assert!(search.progress() > progress);
+            progress = search.progress();
+        }
+        assert_eq!(progress, 1.0);
+        assert_eq!(found.len(), expected.len());
+        for (a, b) in found.iter().zip(&expected) {
+            let dt = Seconds::from(time(a) - time(b));
+            assert!(dt.0.abs() < 1.0, "{:?}", dt);
+        }
+    }
+
+    #[test]
+    fn chunks() {
+        compare(lunar::find_phases, |p: &PhaseEvent| p.timestamp);
+        compare(lunar::find_orbit_events, |e: &OrbitEvent| e.timestamp);
+    }
+}
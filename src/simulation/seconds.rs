@@ -1,5 +1,9 @@
 use std::ops::{Div, Mul};
 
+use chrono::{DateTime, Utc};
+
+use crate::time;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Seconds(pub f64);
 
@@ -12,13 +16,19 @@ impl Seconds {
     }
 
     pub fn to_duration(self) -> chrono::Duration {
-        chrono::Duration::nanoseconds((self.0 * 1e9) as i64)
+        time::duration(self.0)
+    }
+
+    // Physical time between two UTC timestamps, including leap seconds (see
+    // time::elapsed).
+    pub fn between(from: &DateTime<Utc>, to: &DateTime<Utc>) -> Self {
+        Seconds(time::elapsed(from, to))
     }
 }
 
 impl From<chrono::Duration> for Seconds {
     fn from(duration: chrono::Duration) -> Self {
-        Seconds(duration.num_nanoseconds().unwrap() as f64 * 1e-9)
+        Seconds(time::seconds(duration))
     }
 }
 
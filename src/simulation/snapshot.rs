@@ -1,126 +1,81 @@
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 use kiss3d::nalgebra::{Point3, UnitQuaternion, Vector3};
 
-use crate::body::relative_earth_orientation;
+use crate::body::{earth_orientation, Body};
 
-#[derive(Copy, Clone)]
+// BodyState is the position (in km) and velocity (in km/s) of a body.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct BodyState {
+    pub position: Point3<f64>,
+    pub velocity: Vector3<f64>,
+}
+
+impl BodyState {
+    pub fn new(position: Point3<f64>, velocity: Vector3<f64>) -> Self {
+        Self { position, velocity }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
 pub struct Snapshot {
     pub timestamp: DateTime<Utc>,
-    pub earth_position: Point3<f64>,
-    pub earth_velocity: Vector3<f64>,
-    pub moon_position: Point3<f64>,
-    pub moon_velocity: Vector3<f64>,
+    // State of each body, indexed by Body::index().
+    pub bodies: [BodyState; Body::COUNT],
 }
 
-const EARTH_APHELION: f64 = 152.10e6;
-
 impl Snapshot {
-    pub fn solar_eclipse_aug_2017() -> Snapshot {
-        // Data from https://ssd.jpl.nasa.gov/horizons/app.html
-        //   - Vector table
-        //   - Target body: Earth/Luna
-        //   - Coordinate center: @sun
-        //   - Time: 2017-09-21 15:46:48 TDB
-        //
-        // Earth:
-        //   X = 1.290745457486534E+08 Y =-7.899200932997707E+07 Z = 2.689484561856836E+03
-        //   VX= 1.507209745469294E+01 VY= 2.530788781266470E+01 VZ=-2.302676624889699E-03
-        // Moon:
-        //   X = 1.287626991572680E+08 Y =-7.878974778529878E+07 Z = 4.510853649180382E+03
-        //   VX= 1.446304692640349E+01 VY= 2.444380218816157E+01 VZ= 9.547778835418086E-02
-        #[allow(clippy::excessive_precision)]
-        Snapshot {
-            timestamp: Utc.ymd(2017, 8, 21).and_hms(15, 46, 48),
-            earth_position: Point3::new(
-                1.290745457486534E+08,
-                -7.899200932997707E+07,
-                2.689484561856836E+03,
-            ),
-            earth_velocity: Vector3::new(
-                1.507209745469294E+01,
-                2.530788781266470E+01,
-                -2.302676624889699E-03,
-            ),
-            moon_position: Point3::new(
-                1.287626991572680E+08,
-                -7.878974778529878E+07,
-                4.510853649180382E+03,
-            ),
-            moon_velocity: Vector3::new(
-                1.446304692640349E+01,
-                2.444380218816157E+01,
-                9.547778835418086E-02,
-            ),
+    // Creates a snapshot with the given body states. Bodies that are not
+    // specified are at rest at the origin.
+    pub fn new<I>(timestamp: DateTime<Utc>, states: I) -> Self
+    where
+        I: IntoIterator<Item = (Body, BodyState)>,
+    {
+        let mut bodies = [BodyState::default(); Body::COUNT];
+        for (body, state) in states {
+            bodies[body.index()] = state;
         }
+        Snapshot { timestamp, bodies }
     }
 
-    pub fn lunar_eclipse_may_2022() -> Snapshot {
-        // Data obtained as above.
-        // Earth:
-        //   X =-8.698598672690395E+07 Y =-1.237132786667059E+08 Z = 6.674273178458214E+03
-        //   VX= 2.387276880809248E+01 VY=-1.723966486848198E+01 VZ= 1.953072531164501E-03
-        // Moon:
-        //   X =-8.720249905686758E+07 Y =-1.240039134908936E+08 Z = 6.046639699906111E+03
-        //   VX= 2.475907992617419E+01 VY=-1.786132315008197E+01 VZ=-9.810927783777057E-02
-        #[allow(clippy::excessive_precision)]
-        Snapshot {
-            timestamp: Utc.ymd(2022, 5, 16).and_hms(1, 32, 7),
-            earth_position: Point3::new(
-                -8.698598672690395E+07,
-                -1.237132786667059E+08,
-                6.674273178458214E+03,
-            ),
-            earth_velocity: Vector3::new(
-                2.387276880809248E+01,
-                -1.723966486848198E+01,
-                1.953072531164501E-03,
-            ),
-            moon_position: Point3::new(
-                -8.720249905686758E+07,
-                -1.240039134908936E+08,
-                6.046639699906111E+03,
-            ),
-            moon_velocity: Vector3::new(
-                2.475907992617419E+01,
-                -1.786132315008197E+01,
-                -9.810927783777057E-02,
-            ),
-        }
+    pub fn state(&self, body: Body) -> &BodyState {
+        &self.bodies[body.index()]
     }
 
-    #[allow(dead_code)]
-    pub fn test_no_moon_inclination() -> Snapshot {
-        Snapshot {
-            timestamp: Utc.ymd(2000, 1, 1).and_hms(0, 0, 0),
-            earth_position: Point3::new(EARTH_APHELION, 0.0, 0.0),
-            earth_velocity: Vector3::new(0.0, 29.3, 0.0),
-            moon_position: Point3::new(EARTH_APHELION - 372_000.0, 0.0, 0.0),
-            moon_velocity: Vector3::new(0.0, 29.3 - 1.022, 0.0),
-        }
+    pub fn position(&self, body: Body) -> Point3<f64> {
+        self.state(body).position
     }
 
-    #[allow(dead_code)]
-    pub fn test_high_moon_inclination() -> Snapshot {
-        Snapshot {
-            timestamp: Utc.ymd(2000, 1, 1).and_hms(0, 0, 0),
-            earth_position: Point3::new(EARTH_APHELION, 0.0, 0.0),
-            earth_velocity: Vector3::new(0.0, 29.3, 0.0),
-            moon_position: Point3::new(EARTH_APHELION - 372_000.0, 0.0, 3_000.0),
-            moon_velocity: Vector3::new(0.0, 29.3 - 1.022, 0.0),
+    pub fn velocity(&self, body: Body) -> Vector3<f64> {
+        self.state(body).velocity
+    }
+
+    // Returns the snapshot converted to the barycentric frame: the center of
+    // mass of the system is at the origin, at rest.
+    #[must_use]
+    pub fn to_barycentric(mut self) -> Self {
+        let total_mass: f64 = Body::ALL.iter().map(|b| b.mass()).sum();
+        let mut center = Vector3::zeros();
+        let mut center_velocity = Vector3::zeros();
+        for body in Body::ALL {
+            let s = self.state(body);
+            center += s.position.coords * (body.mass() / total_mass);
+            center_velocity += s.velocity * (body.mass() / total_mass);
+        }
+        for s in self.bodies.iter_mut() {
+            s.position -= center;
+            s.velocity -= center_velocity;
         }
+        self
     }
 
+    // Rotation from the Earth-fixed frame (see geo) to the simulation frame.
     pub fn earth_orientation(&self) -> UnitQuaternion<f64> {
-        let angle_around_sun = f64::atan2(self.earth_position.y, self.earth_position.x);
-        UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle_around_sun)
-            * relative_earth_orientation(&self.timestamp)
+        earth_orientation(&self.timestamp)
     }
 
     pub fn moon_orientation(&self) -> UnitQuaternion<f64> {
-        let earth_angle = f64::atan2(
-            self.moon_position.y - self.earth_position.y,
-            self.moon_position.x - self.earth_position.x,
-        );
+        let moon_relative = self.position(Body::Moon) - self.position(Body::Earth);
+        let earth_angle = f64::atan2(moon_relative.y, moon_relative.x);
         UnitQuaternion::from_axis_angle(&Vector3::z_axis(), earth_angle)
     }
 }
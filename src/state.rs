@@ -2,7 +2,10 @@ use chrono::{DateTime, Utc};
 
 use crate::{
     choice::Choice,
-    config::{CameraSpec, Preset},
+    config::{CameraSpec, CameraView, Observer, Preset},
+    eclipse::Eclipse,
+    lunar::{OrbitEvent, PhaseEvent},
+    simulation::{Drift, IntegratorKind, SearchKind, Snapshot},
 };
 
 // SimulationState is a trait used to inform the UI on the current state of the
@@ -10,19 +13,46 @@ use crate::{
 pub trait SimulationState {
     fn preset(&self) -> Choice<Preset>;
     fn timestamp(&self) -> DateTime<Utc>;
+    fn snapshot(&self) -> Snapshot;
     fn is_running(&self) -> bool;
-    fn speed(&self) -> Choice<chrono::Duration>;
+    // Simulated duration per elapsed second.
+    fn speed(&self) -> chrono::Duration;
     fn is_reverse(&self) -> bool;
+    // Whether each frame moves the simulation by the same amount, regardless
+    // of the frame rate.
+    fn is_fixed_step(&self) -> bool;
+    fn integrator(&self) -> Choice<IntegratorKind>;
+    // Drift of the conserved quantities since the preset was loaded.
+    fn drift(&self) -> Drift;
+    // Eclipses found by the last search.
+    fn eclipses(&self) -> &[Eclipse];
+    // Eclipses in the time span of the preset (see Preset::span), as they are
+    // found.
+    fn timeline_eclipses(&self) -> &[Eclipse];
+    // Lunar phases found by the last search.
+    fn phases(&self) -> &[PhaseEvent];
+    // Apsides and nodes of the Moon found by the last search.
+    fn orbit_events(&self) -> &[OrbitEvent];
+    // Fraction of the current seek (see ControlEvent::SeekTo) that is done,
+    // or None if not seeking.
+    fn seek_progress(&self) -> Option<f64>;
+    // Fraction of the current search for the given events (see
+    // ControlEvent::FindEclipses) that is done, or None if not searching.
+    fn search_progress(&self, kind: SearchKind) -> Option<f64>;
 }
 
 // RenderState is a trait used to inform the UI on the current state and
 // settings of the renderer.
 pub trait RenderState {
     fn camera_focus(&self) -> Choice<CameraSpec>;
+    fn camera_view(&self) -> CameraView;
+    fn observer(&self) -> Observer;
     fn show_trails(&self) -> bool;
     fn show_ecliptic(&self) -> bool;
     fn show_skybox(&self) -> bool;
     fn show_earth_axis(&self) -> bool;
+    fn show_eclipse_path(&self) -> bool;
+    fn show_orbit_markers(&self) -> bool;
     fn show_help(&self) -> bool;
     fn fps(&self) -> f64;
 }
@@ -0,0 +1,285 @@
+// Time scales.
+//
+// Timestamps (e.g. Snapshot::timestamp) are always in UTC, which is what the
+// UI shows. UTC is not uniform: leap seconds are inserted from time to time,
+// so the physical time between two UTC timestamps is computed in TDB, the
+// time scale of the JPL ephemerides.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, TimeZone, Utc};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TimeScale {
+    // Coordinated Universal Time.
+    Utc,
+    // International Atomic Time: UTC without the leap seconds.
+    Tai,
+    // Terrestrial Time (formerly TD, dynamical time): TAI + 32.184 s.
+    Tt,
+    // Barycentric Dynamical Time: differs from TT by less than 2 ms.
+    Tdb,
+}
+
+impl TimeScale {
+    pub const ALL: [TimeScale; 4] = [
+        TimeScale::Utc,
+        TimeScale::Tai,
+        TimeScale::Tt,
+        TimeScale::Tdb,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimeScale::Utc => "UTC",
+            TimeScale::Tai => "TAI",
+            TimeScale::Tt => "TT",
+            TimeScale::Tdb => "TDB",
+        }
+    }
+
+    // Looks up a time scale by name (case insensitive).
+    pub fn from_name(name: &str) -> Option<TimeScale> {
+        TimeScale::ALL
+            .into_iter()
+            .find(|s| s.name().eq_ignore_ascii_case(name))
+    }
+
+    // Returns the difference between this time scale and UTC at the given
+    // time, in seconds.
+    pub fn offset(&self, utc: &DateTime<Utc>) -> f64 {
+        match self {
+            TimeScale::Utc => 0.0,
+            TimeScale::Tai => tai_minus_utc(utc),
+            TimeScale::Tt => tai_minus_utc(utc) + TT_MINUS_TAI,
+            TimeScale::Tdb => tai_minus_utc(utc) + TT_MINUS_TAI + tdb_minus_tt(utc),
+        }
+    }
+}
+
+const TT_MINUS_TAI: f64 = 32.184;
+
+// Dates (year, month) from which TAI - UTC has the given value, in seconds.
+// Leap seconds are inserted at the end of the previous day. Source: IERS
+// Bulletin C. Before 1972, UTC was adjusted by fractions of a second; we use
+// the 1972 value.
+const LEAP_SECONDS: [(i32, u32, f64); 28] = [
+    (1972, 1, 10.0),
+    (1972, 7, 11.0),
+    (1973, 1, 12.0),
+    (1974, 1, 13.0),
+    (1975, 1, 14.0),
+    (1976, 1, 15.0),
+    (1977, 1, 16.0),
+    (1978, 1, 17.0),
+    (1979, 1, 18.0),
+    (1980, 1, 19.0),
+    (1981, 7, 20.0),
+    (1982, 7, 21.0),
+    (1983, 7, 22.0),
+    (1985, 7, 23.0),
+    (1988, 1, 24.0),
+    (1990, 1, 25.0),
+    (1991, 1, 26.0),
+    (1992, 7, 27.0),
+    (1993, 7, 28.0),
+    (1994, 7, 29.0),
+    (1996, 1, 30.0),
+    (1997, 7, 31.0),
+    (1999, 1, 32.0),
+    (2006, 1, 33.0),
+    (2009, 1, 34.0),
+    (2012, 7, 35.0),
+    (2015, 7, 36.0),
+    (2017, 1, 37.0),
+];
+
+fn tai_minus_utc(utc: &DateTime<Utc>) -> f64 {
+    let month = (utc.year(), utc.month());
+    LEAP_SECONDS
+        .iter()
+        .rev()
+        .find(|(y, m, _)| month >= (*y, *m))
+        .map_or(LEAP_SECONDS[0].2, |(_, _, offset)| *offset)
+}
+
+// Main periodic terms of TDB - TT, due to the eccentricity of the Earth's
+// orbit (accurate to about 30 microseconds).
+fn tdb_minus_tt(utc: &DateTime<Utc>) -> f64 {
+    let g = (357.53 + 0.98560028 * (julian_date(utc) - J2000)).to_radians();
+    0.001657 * g.sin() + 0.000014 * (2.0 * g).sin()
+}
+
+// Converts seconds to a duration, rounded to the nanosecond. Whole seconds and
+// nanoseconds are converted separately, since a duration of more than about
+// 292 years doesn't fit in i64 nanoseconds.
+pub fn duration(seconds: f64) -> Duration {
+    let whole = seconds.trunc();
+    Duration::seconds(whole as i64)
+        + Duration::nanoseconds(((seconds - whole) * 1e9).round() as i64)
+}
+
+// Converts a duration to seconds, for any duration (see duration).
+pub fn seconds(d: Duration) -> f64 {
+    let whole = d.num_seconds();
+    let nanos = (d - Duration::seconds(whole)).num_nanoseconds().unwrap();
+    whole as f64 + nanos as f64 * 1e-9
+}
+
+// Returns the reading of a clock in the given time scale at the UTC time.
+pub fn to_scale(utc: &DateTime<Utc>, scale: TimeScale) -> NaiveDateTime {
+    utc.naive_utc() + duration(scale.offset(utc))
+}
+
+// Returns the UTC time at which a clock in the given time scale reads t. Times
+// within a leap second are ambiguous, since UTC timestamps can't represent the
+// leap second itself.
+pub fn from_scale(t: &NaiveDateTime, scale: TimeScale) -> DateTime<Utc> {
+    // The offset depends on the UTC time, which is within a minute of t.
+    let mut utc = Utc.from_utc_datetime(t);
+    for _ in 0..2 {
+        utc = Utc.from_utc_datetime(&(*t - duration(scale.offset(&utc))));
+    }
+    utc
+}
+
+// Returns the number of TDB seconds between two UTC times. This is the uniform
+// time used to integrate the motion of the bodies.
+pub fn elapsed(from: &DateTime<Utc>, to: &DateTime<Utc>) -> f64 {
+    seconds(to_scale(to, TimeScale::Tdb) - to_scale(from, TimeScale::Tdb))
+}
+
+// Returns the UTC time after the given number of TDB seconds.
+pub fn add_elapsed(utc: &DateTime<Utc>, seconds: f64) -> DateTime<Utc> {
+    from_scale(
+        &(to_scale(utc, TimeScale::Tdb) + duration(seconds)),
+        TimeScale::Tdb,
+    )
+}
+
+// Parses a UTC timestamp, either in RFC 3339 format or as
+// "YYYY-MM-DD HH:MM[:SS]".
+pub fn parse_utc(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    let s = s.trim().trim_end_matches("UTC").trim();
+    for format in [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S%.f",
+    ] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, format) {
+            return Ok(Utc.from_utc_datetime(&t));
+        }
+    }
+    Err(format!("invalid time '{}'", s))
+}
+
+// Parses a time in the given time scale (in the formats accepted by
+// parse_utc) and converts it to UTC.
+pub fn parse_time(s: &str, scale: TimeScale) -> Result<DateTime<Utc>, String> {
+    Ok(from_scale(&parse_utc(s)?.naive_utc(), scale))
+}
+
+// Julian date of the J2000 epoch (2000-01-01 12:00 TT).
+pub const J2000: f64 = 2451545.0;
+
+// Julian date of the Unix epoch.
+const UNIX_EPOCH: f64 = 2440587.5;
+
+// Returns the Julian date of the timestamp (fractional days since noon on
+// January 1, 4713 BC), in UTC.
+pub fn julian_date(timestamp: &DateTime<Utc>) -> f64 {
+    let seconds = timestamp.timestamp() as f64 + timestamp.timestamp_subsec_nanos() as f64 * 1e-9;
+    UNIX_EPOCH + seconds / 86400.0
+}
+
+// Returns the number of Julian centuries (of 36525 days) of TT since J2000.
+pub fn julian_centuries(timestamp: &DateTime<Utc>) -> f64 {
+    let tt = julian_date(timestamp) + TimeScale::Tt.offset(timestamp) / 86400.0;
+    (tt - J2000) / 36525.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_utc() {
+        let expected = Utc.ymd(2017, 8, 21).and_hms(18, 25, 0);
+        for s in [
+            "2017-08-21 18:25:00",
+            "2017-08-21 18:25",
+            "2017-08-21 18:25 UTC",
+            "2017-08-21T18:25:00Z",
+            "2017-08-21T20:25:00+02:00",
+        ] {
+            assert_eq!(parse_utc(s).unwrap(), expected, "{}", s);
+        }
+        assert!(parse_utc("2017-08-21").is_err());
+    }
+
+    #[test]
+    fn test_julian_date() {
+        // Meeus, example 7.a.
+        let t = parse_utc("1957-10-04 19:26:24").unwrap();
+        assert!((julian_date(&t) - 2436116.31).abs() < 1e-6);
+        let t = parse_utc("2000-01-01 12:00").unwrap();
+        assert_eq!(julian_date(&t), J2000);
+        let t = parse_time("2000-01-01 12:00", TimeScale::Tt).unwrap();
+        assert!(julian_centuries(&t).abs() < 1e-12);
+    }
+
+    #[test]
+    fn scales() {
+        let t = parse_utc("2017-08-21 18:25:00").unwrap();
+        assert_eq!(TimeScale::Tai.offset(&t), 37.0);
+        assert_eq!(TimeScale::Tt.offset(&t), 69.184);
+        // TDB - TT varies by +/-1.7 ms over the year.
+        let tdb = TimeScale::Tdb.offset(&t) - TimeScale::Tt.offset(&t);
+        assert!(tdb > -0.0013 && tdb < -0.0011, "{}", tdb);
+        for scale in TimeScale::ALL {
+            let back = from_scale(&to_scale(&t, scale), scale);
+            assert!(
+                (back - t).num_microseconds().unwrap().abs() < 1,
+                "{:?}",
+                scale
+            );
+            assert_eq!(
+                TimeScale::from_name(&scale.name().to_lowercase()),
+                Some(scale)
+            );
+        }
+        assert_eq!(
+            to_scale(&t, TimeScale::Tai),
+            parse_utc("2017-08-21 18:25:37").unwrap().naive_utc()
+        );
+    }
+
+    #[test]
+    fn leap_seconds() {
+        // A leap second was inserted at the end of 2016.
+        let before = parse_utc("2016-12-31 23:59:59").unwrap();
+        let after = parse_utc("2017-01-01 00:00:00").unwrap();
+        assert_eq!(TimeScale::Tai.offset(&before), 36.0);
+        assert_eq!(TimeScale::Tai.offset(&after), 37.0);
+        assert!((elapsed(&before, &after) - 2.0).abs() < 1e-6);
+        let d = add_elapsed(&before, 2.0) - after;
+        assert!(d.num_microseconds().unwrap().abs() < 1, "{}", d);
+
+        // Before 1972, we use the first value.
+        let t = parse_utc("1960-01-01 00:00").unwrap();
+        assert_eq!(TimeScale::Tai.offset(&t), 10.0);
+    }
+
+    #[test]
+    fn long_durations() {
+        // More than 292 years, which don't fit in i64 nanoseconds.
+        let from = parse_utc("1700-01-01 00:00").unwrap();
+        let to = parse_utc("2017-08-21 18:25:00").unwrap();
+        let days = elapsed(&from, &to) / 86400.0;
+        assert!((days - 116014.768).abs() < 0.001, "{}", days);
+        let d = add_elapsed(&from, elapsed(&from, &to)) - to;
+        assert!(d.num_microseconds().unwrap().abs() < 1, "{}", d);
+        assert_eq!(seconds(duration(-1e10 - 0.25)), -1e10 - 0.25);
+    }
+}